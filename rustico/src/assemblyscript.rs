@@ -0,0 +1,334 @@
+//! AssemblyScript support.
+
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use wasmtime::{Caller, Instance, Memory, Store, Trap, TypedFunc};
+
+use crate::service::{get_memory, Error, HasAbort, Result, WResult};
+
+#[allow(dead_code)]
+const AS_CLASS_ID_OBJECT: u32 = 0;
+const AS_CLASS_ID_BUFFER: u32 = 1;
+const AS_CLASS_ID_STRING: u32 = 2;
+
+#[allow(dead_code, non_snake_case)]
+#[repr(packed)]
+struct AssemblyScriptHeader {
+    /// mmInfo  20  usize   Memory manager info
+    mmInfo: u32,
+    /// gcInfo  16  usize   Garbage collector info
+    gcInfo: u32,
+    /// gcInfo2 12  usize   Garbage collector info
+    gcInfo2: u32,
+    /// rtId    8   u32     Unique id of the concrete class
+    rtId: u32,
+    /// rtSize  4   u32     Size of the data following the header
+    rtSize: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AssemblyScriptObject<'m> {
+    ptr: *const u8,
+    _marker: PhantomData<&'m ()>,
+}
+
+impl<'m> AssemblyScriptObject<'m> {
+    pub(crate) fn from_memory(memory: &'m [u8], ptr: u32) -> Option<Self> {
+        let offset = ptr as usize;
+        if offset > memory.len() {
+            return None;
+        }
+        let header_size = std::mem::size_of::<AssemblyScriptHeader>();
+        let header_offset = offset.checked_sub(header_size)?;
+        let header_ptr = memory[header_offset..offset].as_ptr() as *const AssemblyScriptHeader;
+        let header = if header_ptr.is_aligned() {
+            // Safe to be dereferenced because we have a shared ref to data, but
+            // lifetime is toxic outside of this function.
+            unsafe { &*header_ptr }
+        } else {
+            // Don't think this can ever happen in current AssemblyScript
+            return None;
+        };
+        let size = header.rtSize as usize;
+        if offset + size > memory.len() {
+            return None;
+        }
+        let ptr = memory[offset..].as_ptr() as *const _;
+        Some(Self {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn header(self) -> &'m AssemblyScriptHeader {
+        let header_ptr = unsafe { self.ptr.sub(size_of::<AssemblyScriptHeader>()) };
+        unsafe { &*(header_ptr as *const _) }
+    }
+
+    #[inline]
+    pub(crate) fn payload(self) -> &'m [u8] {
+        let len = self.header().rtSize as usize;
+        unsafe { std::slice::from_raw_parts(self.ptr, len) }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AssemblyScriptString<'m> {
+    inner: AssemblyScriptObject<'m>,
+}
+
+impl<'m> AssemblyScriptString<'m> {
+    pub(crate) fn from_memory(memory: &'m [u8], ptr: u32) -> Option<Self> {
+        let obj = AssemblyScriptObject::from_memory(memory, ptr)?;
+        if obj.header().rtId == AS_CLASS_ID_STRING {
+            Some(Self { inner: obj })
+        } else {
+            None
+        }
+    }
+
+    fn string(self) -> String {
+        // payload pointer is aligned because header is aligned
+        let (prefix, mid, _) = unsafe { self.inner.payload().align_to::<u16>() };
+        if prefix.is_empty() {
+            String::from_utf16_lossy(mid)
+        } else {
+            unreachable!();
+        }
+    }
+}
+
+impl Display for AssemblyScriptString<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.string())
+    }
+}
+
+/// A raw AssemblyScript `ArrayBuffer`: its payload is exactly the bytes the
+/// guest put there, with no further framing.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AssemblyScriptArrayBuffer<'m> {
+    inner: AssemblyScriptObject<'m>,
+}
+
+impl<'m> AssemblyScriptArrayBuffer<'m> {
+    pub(crate) fn from_memory(memory: &'m [u8], ptr: u32) -> Option<Self> {
+        let obj = AssemblyScriptObject::from_memory(memory, ptr)?;
+        if obj.header().rtId == AS_CLASS_ID_BUFFER {
+            Some(Self { inner: obj })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn bytes(self) -> &'m [u8] {
+        self.inner.payload()
+    }
+}
+
+/// A view over an `ArrayBuffer` (e.g. `Uint8Array`, `Float64Array`):
+/// AssemblyScript's `ArrayBufferView` header layout is `buffer: ArrayBuffer,
+/// dataStart: usize, byteLength: u32`, where `dataStart` already points at
+/// the first readable byte and need not equal `buffer`'s payload start
+/// (views may be offset into a larger buffer).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AssemblyScriptTypedArray<'m> {
+    memory: &'m [u8],
+    data_start: u32,
+    byte_length: u32,
+}
+
+impl<'m> AssemblyScriptTypedArray<'m> {
+    pub(crate) fn from_memory(memory: &'m [u8], ptr: u32) -> Option<Self> {
+        let view = AssemblyScriptObject::from_memory(memory, ptr)?;
+        let payload = view.payload();
+        if payload.len() < 12 {
+            return None;
+        }
+        let buffer_ptr = u32::from_le_bytes(payload[0..4].try_into().ok()?);
+        let data_start = u32::from_le_bytes(payload[4..8].try_into().ok()?);
+        let byte_length = u32::from_le_bytes(payload[8..12].try_into().ok()?);
+
+        // `dataStart`/`byteLength` must stay within the referenced buffer's
+        // payload, or this view is corrupt (or not really a view at all).
+        let buffer = AssemblyScriptArrayBuffer::from_memory(memory, buffer_ptr)?;
+        let buffer_bytes = buffer.bytes();
+        let memory_base = memory.as_ptr() as usize;
+        let buffer_start = (buffer_bytes.as_ptr() as usize).checked_sub(memory_base)?;
+        let buffer_end = buffer_start.checked_add(buffer_bytes.len())?;
+        let data_start_offset = data_start as usize;
+        let data_end_offset = data_start_offset.checked_add(byte_length as usize)?;
+        if data_start_offset < buffer_start || data_end_offset > buffer_end {
+            return None;
+        }
+
+        Some(Self {
+            memory,
+            data_start,
+            byte_length,
+        })
+    }
+
+    pub(crate) fn bytes(self) -> &'m [u8] {
+        let start = self.data_start as usize;
+        let end = start + self.byte_length as usize;
+        &self.memory[start..end]
+    }
+
+    /// Interprets the view's bytes as a `Float64Array`.
+    pub(crate) fn as_f64(self) -> Vec<f64> {
+        self.bytes()
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+}
+
+fn instance_memory<T>(store: &mut Store<T>, instance: &Instance) -> Result<Memory> {
+    instance
+        .get_memory(&mut *store, "memory")
+        .ok_or(Error::MemoryNotExported)
+}
+
+/// Allocates guest objects by calling back into a module's
+/// AssemblyScript-generated `__new`/`__pin`/`__unpin` exports, so host code
+/// can pass a `String` as a call argument instead of being limited to the
+/// flat numeric ABI.
+pub(crate) struct AssemblyScriptAllocator {
+    instance: Instance,
+    new_fn: TypedFunc<(i32, i32), i32>,
+    pin_fn: TypedFunc<i32, i32>,
+    unpin_fn: TypedFunc<i32, ()>,
+}
+
+impl AssemblyScriptAllocator {
+    /// Resolve `__new`/`__pin`/`__unpin` from `instance`, failing with
+    /// [`Error::FunctionNotFound`] if the module doesn't export them (e.g.
+    /// it isn't AssemblyScript, or was built with `--exportRuntime` off).
+    pub(crate) fn resolve<T>(store: &mut Store<T>, instance: Instance) -> Result<Self> {
+        let new_fn = instance
+            .get_typed_func(&mut *store, "__new")
+            .map_err(|_| Error::FunctionNotFound)?;
+        let pin_fn = instance
+            .get_typed_func(&mut *store, "__pin")
+            .map_err(|_| Error::FunctionNotFound)?;
+        let unpin_fn = instance
+            .get_typed_func(&mut *store, "__unpin")
+            .map_err(|_| Error::FunctionNotFound)?;
+        Ok(Self {
+            instance,
+            new_fn,
+            pin_fn,
+            unpin_fn,
+        })
+    }
+
+    /// Allocates a guest `String`, copies `text`'s UTF-16 encoding into it,
+    /// pins it against garbage collection, and returns a pointer usable as
+    /// a call argument. The caller must [`Self::unpin`] it once the module
+    /// no longer needs it.
+    pub(crate) async fn write_string<T: Send>(&self, store: &mut Store<T>, text: &str) -> Result<u32> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let size = i32::try_from(units.len() * 2).map_err(|_| Error::InvalidPointer)?;
+        let ptr = self
+            .new_fn
+            .call_async(&mut *store, (size, AS_CLASS_ID_STRING as i32))
+            .await
+            .map_err(Error::Wasm)?;
+        self.pin_fn
+            .call_async(&mut *store, ptr)
+            .await
+            .map_err(Error::Wasm)?;
+
+        let memory = instance_memory(store, &self.instance)?;
+        let mut bytes = Vec::with_capacity(units.len() * 2);
+        for unit in units {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        memory
+            .write(&mut *store, ptr as usize, &bytes)
+            .map_err(|_| Error::InvalidPointer)?;
+
+        Ok(ptr as u32)
+    }
+
+    /// Releases the pin taken by [`Self::write_string`], letting the guest
+    /// GC reclaim the object once nothing else references it.
+    pub(crate) async fn unpin<T: Send>(&self, store: &mut Store<T>, ptr: u32) -> Result<()> {
+        self.unpin_fn
+            .call_async(&mut *store, ptr as i32)
+            .await
+            .map_err(Error::Wasm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_SIZE: usize = size_of::<AssemblyScriptHeader>();
+
+    /// Writes an `AssemblyScriptHeader` for an object whose payload starts
+    /// at `ptr`, and returns `ptr` back for convenience.
+    fn write_object(memory: &mut [u8], ptr: usize, rt_id: u32, rt_size: u32) -> u32 {
+        let header_offset = ptr - HEADER_SIZE;
+        memory[header_offset..header_offset + 4].copy_from_slice(&0u32.to_ne_bytes()); // mmInfo
+        memory[header_offset + 4..header_offset + 8].copy_from_slice(&0u32.to_ne_bytes()); // gcInfo
+        memory[header_offset + 8..header_offset + 12].copy_from_slice(&0u32.to_ne_bytes()); // gcInfo2
+        memory[header_offset + 12..header_offset + 16].copy_from_slice(&rt_id.to_ne_bytes());
+        memory[header_offset + 16..header_offset + 20].copy_from_slice(&rt_size.to_ne_bytes());
+        ptr as u32
+    }
+
+    #[test]
+    fn string_rejects_an_object_with_the_wrong_class_id() {
+        let mut memory = vec![0u8; 64];
+        let ptr = write_object(&mut memory, HEADER_SIZE, AS_CLASS_ID_BUFFER, 0);
+
+        assert!(AssemblyScriptString::from_memory(&memory, ptr).is_none());
+    }
+
+    #[test]
+    fn array_buffer_rejects_an_object_with_the_wrong_class_id() {
+        let mut memory = vec![0u8; 64];
+        let ptr = write_object(&mut memory, HEADER_SIZE, AS_CLASS_ID_STRING, 0);
+
+        assert!(AssemblyScriptArrayBuffer::from_memory(&memory, ptr).is_none());
+    }
+
+    #[test]
+    fn typed_array_rejects_a_view_that_overruns_its_buffer() {
+        let mut memory = vec![0u8; 128];
+        let buffer_ptr = write_object(&mut memory, HEADER_SIZE, AS_CLASS_ID_BUFFER, 16);
+
+        let view_ptr = HEADER_SIZE + 16 + HEADER_SIZE;
+        memory[view_ptr..view_ptr + 4].copy_from_slice(&buffer_ptr.to_le_bytes());
+        // data_start/byte_length run 14 bytes past the 16-byte buffer payload.
+        let data_start = buffer_ptr + 10;
+        let byte_length = 20u32;
+        memory[view_ptr + 4..view_ptr + 8].copy_from_slice(&data_start.to_le_bytes());
+        memory[view_ptr + 8..view_ptr + 12].copy_from_slice(&byte_length.to_le_bytes());
+        let view_ptr = write_object(&mut memory, view_ptr, AS_CLASS_ID_OBJECT, 12);
+
+        assert!(AssemblyScriptTypedArray::from_memory(&memory, view_ptr).is_none());
+    }
+}
+
+pub(crate) fn env_abort<T: HasAbort>(
+    mut caller: Caller<'_, T>,
+    message_ptr: u32,
+    file_name_ptr: u32,
+    line: u32,
+    column: u32,
+) -> WResult<()> {
+    let (memory, runtime_data) = get_memory(&mut caller)?.data_and_store_mut(&mut caller);
+    let message =
+        AssemblyScriptString::from_memory(memory, message_ptr).ok_or(Error::InvalidPointer)?;
+    let file_name =
+        AssemblyScriptString::from_memory(memory, file_name_ptr).ok_or(Error::InvalidPointer)?;
+    runtime_data.record_abort(format!("env.abort {message} {file_name}:{line}:{column}"));
+    Err(Trap::Interrupt.into())
+}
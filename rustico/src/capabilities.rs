@@ -0,0 +1,84 @@
+//! Per-module capability/permission policy, modeled on Deno's
+//! `permissions::Permissions`: host imports consult the calling module's
+//! [`Capabilities`] before acting, so a policy can grant a trusted builtin
+//! more than it grants an untrusted web-loaded module.
+
+use std::time::Duration;
+
+/// What a module is allowed to do, and within what resource ceilings.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Whether the module may write through `wotto.output` at all.
+    /// AssemblyScript `print` is not gated by this: it only logs to the
+    /// host's stdout and never consults the module's capabilities.
+    pub allow_output: bool,
+    /// Maximum cumulative bytes the module may write via `wotto.output`.
+    pub output_budget: usize,
+    /// Whether the module may be served by a host import that fetches on
+    /// its behalf. Reserved for when such an import exists.
+    pub allow_web_fetch: bool,
+    /// Wall-clock budget for a single `run_module` call.
+    pub max_wall_time: Duration,
+    /// Linear memory ceiling, passed to `StoreLimitsBuilder::memory_size`.
+    pub max_memory_bytes: usize,
+    /// Table element ceiling, passed to `StoreLimitsBuilder::table_elements`.
+    pub max_table_elements: usize,
+    /// Whether the module may import `wasi_snapshot_preview1` (clocks,
+    /// random, args, stdio) alongside `wotto.*`. Preview1 exposes all of
+    /// these under one ABI namespace, so there's no finer-grained way to
+    /// allow e.g. clocks but not random; a module that imports it while this
+    /// is `false` is refused with [`crate::service::Error::WasiNotAllowed`]
+    /// instead of being instantiated.
+    pub allow_wasi: bool,
+    /// Whether directories are preopened for the module's WASI filesystem
+    /// calls. Reserved for when per-module preopen configuration exists;
+    /// has no effect yet, since no directories are ever preopened.
+    pub allow_wasi_filesystem: bool,
+    /// Starting fuel budget for a single `run_module` call, passed to
+    /// `Store::set_fuel`. A tight compute loop exhausts this long before
+    /// `max_wall_time` would trip, so it's a tighter, deterministic
+    /// complement to the epoch timeout rather than a replacement for it.
+    pub max_fuel: u64,
+}
+
+impl Capabilities {
+    /// The policy this host applied to every module before per-module
+    /// policies existed: generous ceilings suitable for trusted,
+    /// path-loaded "builtin" modules.
+    pub fn builtin() -> Self {
+        Self {
+            allow_output: true,
+            output_budget: 512,
+            allow_web_fetch: false,
+            max_wall_time: Duration::from_millis(5000),
+            max_memory_bytes: 1 << 20,
+            max_table_elements: 10 << 10,
+            allow_wasi: true,
+            allow_wasi_filesystem: true,
+            max_fuel: 10_000_000_000,
+        }
+    }
+
+    /// A tighter policy for modules loaded from the web: same wall-clock
+    /// and memory ceilings as [`Capabilities::builtin`], but a smaller
+    /// output budget and a smaller fuel budget. Unlike `builtin`, WASI is
+    /// denied by default: preview1 only gates as one namespace (see
+    /// [`Capabilities::allow_wasi`]), so an untrusted module can't be
+    /// handed clocks/random/stdio without also being handed everything
+    /// else under that ABI; it has to ask for `allow_wasi` explicitly.
+    pub fn sandboxed() -> Self {
+        Self {
+            output_budget: 256,
+            allow_wasi: false,
+            allow_wasi_filesystem: false,
+            max_fuel: 1_000_000_000,
+            ..Self::builtin()
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
@@ -0,0 +1,86 @@
+//! On-disk cache of precompiled wasm modules, keyed by the SHA-256 digest
+//! of the source bytes they were compiled from (see [`crate::lockfile::digest`]).
+//! Complements the in-memory digest-keyed registry in [`crate::service`]:
+//! that one survives within a process, this one survives a restart, turning
+//! a reload of unchanged bytes into an `unsafe` [`Module::deserialize`]
+//! instead of a full Cranelift recompile.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use wasmtime::{Engine, Module};
+
+use crate::service::{Error, Result};
+
+const DEFAULT_CACHE_DIR: &str = "wotto-cache/modules";
+const COMPAT_HASH_FILE: &str = "compat-hash";
+
+pub(crate) struct CompiledModuleCache {
+    dir: PathBuf,
+}
+
+impl CompiledModuleCache {
+    /// Open (creating if necessary) an on-disk cache for `engine` at `dir`.
+    /// If `dir` already holds artifacts precompiled by a build of wasmtime
+    /// whose `Engine::precompile_compatibility_hash` doesn't match `engine`'s
+    /// (e.g. after a wasmtime upgrade), they're discarded: the `unsafe`
+    /// deserialize fast path is only as safe as that compatibility check.
+    pub(crate) fn open(engine: &Engine, dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let compat_path = dir.join(COMPAT_HASH_FILE);
+        let current_hash = compatibility_hash(engine);
+        let stale = std::fs::read_to_string(&compat_path)
+            .map(|stored| stored != current_hash)
+            .unwrap_or(true);
+        if stale {
+            for entry in std::fs::read_dir(&dir)?.flatten() {
+                if entry.path().extension().and_then(|ext| ext.to_str()) == Some("cwasm") {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+            std::fs::write(&compat_path, &current_hash)?;
+        }
+        Ok(Self { dir })
+    }
+
+    /// Open the default cache directory, used by [`crate::Service::new`].
+    pub(crate) fn open_default(engine: &Engine) -> std::io::Result<Self> {
+        Self::open(engine, DEFAULT_CACHE_DIR)
+    }
+
+    fn path_for(&self, digest: &str) -> PathBuf {
+        self.dir.join(format!("{digest}.cwasm"))
+    }
+
+    /// Look up `digest`'s precompiled artifact on disk; on a miss (or a
+    /// deserialize failure, e.g. a stale artifact that slipped past the
+    /// compatibility check somehow), compile `bytes` fresh and persist the
+    /// result under `digest` for next time.
+    pub(crate) fn load_or_compile(&self, engine: &Engine, digest: &str, bytes: &[u8]) -> Result<Module> {
+        let path = self.path_for(digest);
+        if let Ok(serialized) = std::fs::read(&path) {
+            // Safety: `serialized` was produced by `Module::serialize` for an
+            // engine whose compatibility hash matched `engine`'s, checked by
+            // `Self::open` before this cache is ever consulted.
+            if let Ok(module) = unsafe { Module::deserialize(engine, &serialized) } {
+                return Ok(module);
+            }
+        }
+        let module = Module::new(engine, bytes).map_err(Error::Wasm)?;
+        if let Ok(serialized) = module.serialize() {
+            let _ = std::fs::write(&path, serialized);
+        }
+        Ok(module)
+    }
+}
+
+/// `Engine::precompile_compatibility_hash` returns an opaque `impl Hash`
+/// rather than something directly persistable, so fold it into a plain
+/// hex-encoded `u64` we can stash in a marker file.
+fn compatibility_hash(engine: &Engine) -> String {
+    let mut hasher = DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
@@ -0,0 +1,207 @@
+//! Tunables for the wasmtime [`wasmtime::Engine`] a [`crate::Service`]
+//! wraps, including JIT profiling integration so operators can see which
+//! modules and functions dominate CPU when many modules run under the
+//! `Throttler`.
+
+use wasmtime::{
+    Config, InstanceAllocationStrategy, OptLevel, PoolingAllocationConfig,
+    ProfilingStrategy as WasmtimeProfilingStrategy, WasmBacktraceDetails,
+};
+
+/// Which JIT profiling integration `wasmtime` should enable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfilingStrategy {
+    #[default]
+    None,
+    /// Emit `/tmp/perf-<pid>.map` for Linux `perf` symbolication.
+    PerfMap,
+    /// Emit jitdump output, for `perf report --annotate` or `perf inject`.
+    JitDump,
+    /// Report JIT events to Intel VTune. Mirrors wasmtime's own platform
+    /// gating for the `ittapi`-backed strategy: x86_64 only, and not on
+    /// Android or mingw.
+    #[cfg(all(
+        feature = "vtune",
+        target_arch = "x86_64",
+        not(target_os = "android"),
+        not(all(target_os = "windows", target_env = "gnu")),
+    ))]
+    VTune,
+}
+
+impl ProfilingStrategy {
+    /// Reads the strategy an operator wants from the `RUSTICO_PROFILE`
+    /// environment variable (`perfmap`, `jitdump`, or `vtune`), the same
+    /// "operator sets an env var, there's no CLI flag for it" idiom used by
+    /// [`crate::webload::credentials`]'s `GITHUB_TOKEN`. Defaults to
+    /// [`ProfilingStrategy::None`] if the variable is unset, names a
+    /// strategy this crate doesn't recognize, or names `vtune` on a build
+    /// that can't provide it.
+    pub fn from_env() -> Self {
+        match std::env::var("RUSTICO_PROFILE").as_deref() {
+            Ok("perfmap") => Self::PerfMap,
+            Ok("jitdump") => Self::JitDump,
+            Ok("vtune") => Self::vtune_or_fallback(),
+            Ok(other) => {
+                tracing::warn!(requested = other, "unrecognized RUSTICO_PROFILE, profiling disabled");
+                Self::None
+            }
+            Err(_) => Self::None,
+        }
+    }
+
+    #[cfg(all(
+        feature = "vtune",
+        target_arch = "x86_64",
+        not(target_os = "android"),
+        not(all(target_os = "windows", target_env = "gnu")),
+    ))]
+    fn vtune_or_fallback() -> Self {
+        Self::VTune
+    }
+
+    /// VTune was requested but this build can't provide it (either the
+    /// `vtune` feature is off, or ittapi doesn't support this platform) —
+    /// fall back to no profiling rather than failing to start.
+    #[cfg(not(all(
+        feature = "vtune",
+        target_arch = "x86_64",
+        not(target_os = "android"),
+        not(all(target_os = "windows", target_env = "gnu")),
+    )))]
+    fn vtune_or_fallback() -> Self {
+        tracing::warn!("vtune profiling requested but unavailable on this build, profiling disabled");
+        Self::None
+    }
+}
+
+impl From<ProfilingStrategy> for WasmtimeProfilingStrategy {
+    fn from(strategy: ProfilingStrategy) -> Self {
+        match strategy {
+            ProfilingStrategy::None => WasmtimeProfilingStrategy::None,
+            ProfilingStrategy::PerfMap => WasmtimeProfilingStrategy::PerfMap,
+            ProfilingStrategy::JitDump => WasmtimeProfilingStrategy::JitDump,
+            #[cfg(all(
+                feature = "vtune",
+                target_arch = "x86_64",
+                not(target_os = "android"),
+                not(all(target_os = "windows", target_env = "gnu")),
+            ))]
+            ProfilingStrategy::VTune => WasmtimeProfilingStrategy::VTune,
+        }
+    }
+}
+
+/// Sizing for wasmtime's pooling instance allocator, which pre-reserves
+/// memory/table mappings up front and recycles them via copy-on-write
+/// resets between instantiations instead of `mmap`-ing fresh ones every
+/// `run_module` call. The per-slot ceilings default to the same figures
+/// every [`crate::capabilities::Capabilities`] policy already enforces
+/// (`max_memory_bytes`/`max_table_elements`), so the pool reserves exactly
+/// as much as a run is ever allowed to use; `total_memories`/`total_tables`/
+/// `max_instances` bound how many concurrent instances the pool keeps slots
+/// for.
+#[derive(Debug, Clone)]
+pub struct PoolingConfig {
+    pub max_memory_bytes: usize,
+    pub max_table_elements: u32,
+    pub total_memories: u32,
+    pub total_tables: u32,
+    pub max_instances: u32,
+}
+
+impl PoolingConfig {
+    fn to_wasmtime(&self) -> PoolingAllocationConfig {
+        let mut pooling = PoolingAllocationConfig::new();
+        pooling
+            .max_memory_size(self.max_memory_bytes)
+            .table_elements(self.max_table_elements)
+            .total_memories(self.total_memories)
+            .total_tables(self.total_tables)
+            .total_core_instances(self.max_instances);
+        pooling
+    }
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: 1 << 20,
+            max_table_elements: 10 << 10,
+            total_memories: 100,
+            total_tables: 100,
+            max_instances: 100,
+        }
+    }
+}
+
+/// How the engine should allocate wasm instances.
+#[derive(Debug, Clone)]
+pub enum InstanceAllocation {
+    /// Pre-reserve and recycle memory/table mappings via wasmtime's pooling
+    /// allocator. Much faster per-instantiation, but reserves virtual
+    /// address space up front, which some sandboxes/containers refuse to
+    /// grant.
+    Pooling(PoolingConfig),
+    /// Allocate fresh mappings per instantiation, wasmtime's default. Use
+    /// this where the pooling allocator's up-front reservation isn't
+    /// available.
+    OnDemand,
+}
+
+impl Default for InstanceAllocation {
+    fn default() -> Self {
+        Self::Pooling(PoolingConfig::default())
+    }
+}
+
+impl From<InstanceAllocation> for InstanceAllocationStrategy {
+    fn from(allocation: InstanceAllocation) -> Self {
+        match allocation {
+            InstanceAllocation::Pooling(pooling) => {
+                InstanceAllocationStrategy::Pooling(pooling.to_wasmtime())
+            }
+            InstanceAllocation::OnDemand => InstanceAllocationStrategy::OnDemand,
+        }
+    }
+}
+
+/// Engine-level configuration for a [`crate::Service`].
+#[derive(Debug, Clone)]
+pub struct ServiceConfig {
+    pub debug_info: bool,
+    pub wasm_backtrace_details: bool,
+    pub profiling: ProfilingStrategy,
+    pub instance_allocation: InstanceAllocation,
+}
+
+impl ServiceConfig {
+    pub(crate) fn to_wasmtime_config(&self) -> Config {
+        let mut config = Config::new();
+        config
+            .debug_info(self.debug_info)
+            .wasm_backtrace_details(if self.wasm_backtrace_details {
+                WasmBacktraceDetails::Enable
+            } else {
+                WasmBacktraceDetails::Disable
+            })
+            .async_support(true)
+            .epoch_interruption(true)
+            .consume_fuel(true)
+            .cranelift_opt_level(OptLevel::Speed)
+            .profiler(self.profiling.into())
+            .allocation_strategy(self.instance_allocation.clone().into());
+        config
+    }
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            debug_info: true,
+            wasm_backtrace_details: true,
+            profiling: ProfilingStrategy::from_env(),
+            instance_allocation: InstanceAllocation::default(),
+        }
+    }
+}
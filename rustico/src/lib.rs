@@ -1,11 +1,19 @@
 #![feature(pointer_is_aligned)]
 
 mod assemblyscript;
+mod capabilities;
+mod compiled_cache;
+mod config;
+mod lockfile;
 mod registry;
 #[cfg(feature = "repl")]
 pub mod repl;
 mod runtime;
 mod service;
+mod wasi;
+mod watcher;
 mod webload;
 
-pub use service::{Command, Error, Service};
+pub use capabilities::Capabilities;
+pub use config::{InstanceAllocation, PoolingConfig, ProfilingStrategy, ServiceConfig};
+pub use service::{Command, Error, ModuleInfo, RescanReport, Service, TestOutcome, TestResult};
@@ -0,0 +1,70 @@
+//! Persisted SHA-256 integrity lockfile for web-loaded modules, in the
+//! spirit of Deno's lock file: the hash recorded the first time a module is
+//! loaded under a given fqn is checked against every subsequent reload, so a
+//! module that changed upstream (maliciously or not) is caught instead of
+//! silently swapped in.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+const DEFAULT_LOCKFILE_PATH: &str = "wotto-lock.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LockEntry {
+    pub(crate) url: String,
+    pub(crate) hash: String,
+}
+
+/// The hex-encoded SHA-256 digest of `bytes`.
+pub(crate) fn digest(bytes: &[u8]) -> String {
+    let hash = Sha256::digest(bytes);
+    let mut hex = String::with_capacity(hash.len() * 2);
+    for byte in hash {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    hex
+}
+
+pub(crate) struct Lockfile {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, LockEntry>>,
+}
+
+impl Lockfile {
+    pub(crate) fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// The recorded entry for `fqn`, if any.
+    pub(crate) async fn get(&self, fqn: &str) -> Option<LockEntry> {
+        self.entries.lock().await.get(fqn).cloned()
+    }
+
+    /// Record (or overwrite) `fqn`'s entry and persist the lockfile to disk.
+    pub(crate) async fn record(&self, fqn: String, entry: LockEntry) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().await;
+        entries.insert(fqn, entry);
+        let json = serde_json::to_string_pretty(&*entries)
+            .expect("lockfile entries should always serialize");
+        std::fs::write(&self.path, json)
+    }
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self::load(DEFAULT_LOCKFILE_PATH)
+    }
+}
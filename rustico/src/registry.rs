@@ -1,35 +1,37 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
 
 use tokio::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use crate::webload::ResolvedModule;
+use crate::service::Result;
 
-/// Reference to a registered module. Holds a read guard.
-pub(crate) struct ModuleRef<'a> {
-    inner: RwLockReadGuard<'a, Option<ResolvedModule>>,
+/// Reference to a registered value. Holds a read guard.
+pub(crate) struct ModuleRef<'a, V> {
+    inner: RwLockReadGuard<'a, Option<V>>,
 }
 
-impl<'a> ModuleRef<'a> {
-    fn new(inner: RwLockReadGuard<'a, Option<ResolvedModule>>) -> Self {
+impl<'a, V> ModuleRef<'a, V> {
+    fn new(inner: RwLockReadGuard<'a, Option<V>>) -> Self {
         debug_assert!(inner.is_some(), "ModuleRef cannot be initialized with None");
         Self { inner }
     }
 }
 
-impl<'a> std::ops::Deref for ModuleRef<'a> {
-    type Target = ResolvedModule;
+impl<'a, V> std::ops::Deref for ModuleRef<'a, V> {
+    type Target = V;
 
     fn deref(&self) -> &Self::Target {
         self.inner.as_ref().unwrap()
     }
 }
 
-pub(crate) struct ModuleRefMut<'a> {
-    inner: RwLockWriteGuard<'a, Option<ResolvedModule>>,
+pub(crate) struct ModuleRefMut<'a, V> {
+    inner: RwLockWriteGuard<'a, Option<V>>,
 }
 
-impl<'a> ModuleRefMut<'a> {
-    fn new(inner: RwLockWriteGuard<'a, Option<ResolvedModule>>) -> Self {
+impl<'a, V> ModuleRefMut<'a, V> {
+    fn new(inner: RwLockWriteGuard<'a, Option<V>>) -> Self {
         debug_assert!(
             inner.is_some(),
             "ModuleRefMut cannot be initialized with None"
@@ -38,26 +40,36 @@ impl<'a> ModuleRefMut<'a> {
     }
 }
 
-impl<'a> std::ops::Deref for ModuleRefMut<'a> {
-    type Target = ResolvedModule;
+impl<'a, V> std::ops::Deref for ModuleRefMut<'a, V> {
+    type Target = V;
 
     fn deref(&self) -> &Self::Target {
         self.inner.as_ref().unwrap()
     }
 }
 
-impl<'a> std::ops::DerefMut for ModuleRefMut<'a> {
+impl<'a, V> std::ops::DerefMut for ModuleRefMut<'a, V> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.inner.as_mut().unwrap()
     }
 }
 
-struct ModuleEntry {
-    module: RwLock<Option<ResolvedModule>>,
+/// What a cache-filling fetch should do with the value it already has, if
+/// any. See [`Registry::get_or_try_update_with`].
+pub(crate) enum FetchOutcome<V> {
+    /// Keep serving the existing cached value (e.g. the remote told us it
+    /// hasn't changed).
+    Reuse,
+    /// Replace the cached value with this one.
+    Replace(V),
 }
 
-impl ModuleEntry {
-    fn with_module(module: ResolvedModule) -> Self {
+struct ModuleEntry<V> {
+    module: RwLock<Option<V>>,
+}
+
+impl<V> ModuleEntry<V> {
+    fn with_module(module: V) -> Self {
         Self {
             module: RwLock::new(Some(module)),
         }
@@ -69,13 +81,13 @@ impl ModuleEntry {
         }
     }
 
-    async fn replace(&self, module: ResolvedModule) -> (ModuleRef, Option<ResolvedModule>) {
+    async fn replace(&self, module: V) -> (ModuleRef<V>, Option<V>) {
         let mut guard = self.module.write().await;
         let old = guard.replace(module);
         (ModuleRef::new(guard.downgrade()), old)
     }
 
-    async fn lock(&self) -> Option<ModuleRefMut> {
+    async fn lock(&self) -> Option<ModuleRefMut<V>> {
         let guard = self.module.write().await;
         match *guard {
             Some(_) => Some(ModuleRefMut::new(guard)),
@@ -84,31 +96,31 @@ impl ModuleEntry {
     }
 }
 
-impl From<ResolvedModule> for ModuleEntry {
-    fn from(value: ResolvedModule) -> Self {
+impl<V> From<V> for ModuleEntry<V> {
+    fn from(value: V) -> Self {
         Self::with_module(value)
     }
 }
 
-pub(crate) struct Registry {
-    modules: Mutex<HashMap<String, ModuleEntry>>,
+pub(crate) struct Registry<K, V> {
+    modules: Mutex<HashMap<K, ModuleEntry<V>>>,
 }
 
-impl Registry {
-    async fn entry_or_default(&self, key: String) -> &ModuleEntry {
+impl<K: Eq + Hash + Clone, V> Registry<K, V> {
+    async fn entry_or_default(&self, key: K) -> &ModuleEntry<V> {
         // Since we never remove a ModuleEntry, we can force the lifetime to be
         // the same as self. I would like to do this without unsafe if possible
         // but can't think of a way. Since we are downgrading a mut ref to a
         // shared ref, but we are messing with the lifetime, we cannot ever
         // use a (safe) mut ref to the entry anytime again.
         let mut map = self.modules.lock().await;
-        let entry: &ModuleEntry = map.entry(key).or_insert_with(ModuleEntry::empty);
+        let entry: &ModuleEntry<V> = map.entry(key).or_insert_with(ModuleEntry::empty);
         // Safety: no mutable references are ever created, and the entry is
         // only ever dropped if the Registry is dropped.
         unsafe { std::mem::transmute(entry) }
     }
 
-    async fn entry(&self, key: &str) -> Option<&ModuleEntry> {
+    async fn entry(&self, key: &K) -> Option<&ModuleEntry<V>> {
         // Similarly to entry_or_default() we force the lifetime. But we only
         // want a reference if the entry actually exists. This can save the
         // caller to allocate/copy the key.
@@ -118,11 +130,7 @@ impl Registry {
             .map(|entry| unsafe { std::mem::transmute(entry) })
     }
 
-    pub(crate) async fn register(
-        &self,
-        name: String,
-        module: ResolvedModule,
-    ) -> (ModuleRef, Option<ResolvedModule>) {
+    pub(crate) async fn register(&self, name: K, module: V) -> (ModuleRef<V>, Option<V>) {
         // let entry = {
         //     self.modules.lock().await.entry(name).or_insert_with(ModuleEntry::empty)
         // };
@@ -132,15 +140,61 @@ impl Registry {
         entry.replace(module).await
     }
 
-    pub(crate) async fn lock_entry(&self, name: &str) -> Option<ModuleRefMut> {
-        match self.entry(name).await {
+    pub(crate) async fn lock_entry(&self, name: K) -> Option<ModuleRefMut<V>> {
+        match self.entry(&name).await {
             Some(entry) => entry.lock().await,
             None => None,
         }
     }
 }
 
-impl Default for Registry {
+impl<K: Eq + Hash + Clone, V: Clone> Registry<K, V> {
+    /// Return the cached value for `key`, or populate it by awaiting `fetch`
+    /// if this is the first time it's requested.
+    ///
+    /// The per-key lock is held for the duration of `fetch`, so concurrent
+    /// callers for the same `key` coalesce into a single in-flight fetch
+    /// instead of racing the network.
+    pub(crate) async fn get_or_try_insert_with<F, Fut>(&self, key: K, fetch: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        let entry = self.entry_or_default(key).await;
+        let mut guard = entry.module.write().await;
+        if let Some(value) = &*guard {
+            return Ok(value.clone());
+        }
+        let value = fetch().await?;
+        *guard = Some(value.clone());
+        Ok(value)
+    }
+
+    /// Like [`Self::get_or_try_insert_with`], but `fetch` always runs and
+    /// decides, given the previously cached value (if any), whether to keep
+    /// it ([`FetchOutcome::Reuse`]) or replace it ([`FetchOutcome::Replace`]).
+    /// Useful for conditional revalidation (e.g. HTTP `ETag`/`304`).
+    pub(crate) async fn get_or_try_update_with<F, Fut>(&self, key: K, fetch: F) -> Result<V>
+    where
+        F: FnOnce(Option<V>) -> Fut,
+        Fut: Future<Output = Result<FetchOutcome<V>>>,
+    {
+        let entry = self.entry_or_default(key).await;
+        let mut guard = entry.module.write().await;
+        let previous = guard.clone();
+        match fetch(previous).await? {
+            FetchOutcome::Reuse => Ok(guard
+                .clone()
+                .expect("FetchOutcome::Reuse requires a previously cached value to reuse")),
+            FetchOutcome::Replace(value) => {
+                *guard = Some(value.clone());
+                Ok(value)
+            }
+        }
+    }
+}
+
+impl<K, V> Default for Registry<K, V> {
     fn default() -> Self {
         Self {
             modules: Default::default(),
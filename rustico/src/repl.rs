@@ -74,6 +74,24 @@ fn parse_command(cmd: String) -> Option<Command> {
     let args: Vec<_> = cmd.split_whitespace().collect();
     match &args[..] {
         ["load", module] => Some(Command::LoadModule(module.to_string())),
+        ["watch", "on"] => Some(Command::Watch(true)),
+        ["watch", "off"] => Some(Command::Watch(false)),
+        ["test", module] => Some(Command::TestModule {
+            module: module.to_string(),
+            filter: None,
+        }),
+        ["test", module, filter] => Some(Command::TestModule {
+            module: module.to_string(),
+            filter: Some(filter.to_string()),
+        }),
+        ["trust", module] => Some(Command::SetModulePolicy {
+            module: module.to_string(),
+            sandboxed: false,
+        }),
+        ["sandbox", module] => Some(Command::SetModulePolicy {
+            module: module.to_string(),
+            sandboxed: true,
+        }),
         ["run", module, entry_point, ..] => Some(Command::RunModule {
             module: module.to_string(),
             entry_point: entry_point.to_string(),
@@ -1,7 +1,7 @@
 //! Functions exported to WASM modules.
 
 use crate::assemblyscript::{env_abort, AssemblyScriptString};
-use crate::service::{get_memory, Error, HasInput, HasOutput, WResult};
+use crate::service::{get_memory, Error, HasAbort, HasInput, HasOutput, HasWasi, WResult};
 use wasmtime::*;
 
 /// AssemblyScript-style print
@@ -23,8 +23,7 @@ fn output<T: HasOutput>(mut caller: Caller<'_, T>, ptr: u32, len: u32) -> WResul
     let strdata = &memory[offset..][..size];
     let txt = std::str::from_utf8(strdata)?;
     println!("wotto.output {txt}");
-    runtime_data.output(txt);
-    Ok(())
+    runtime_data.output(txt)
 }
 
 fn input<T: HasInput>(mut caller: Caller<'_, T>, ptr: u32, len: u32) -> WResult<u32> {
@@ -50,7 +49,7 @@ pub(crate) fn add_to_linker<T>(
     enable_assembly_script_support: bool,
 ) -> WResult<()>
 where
-    T: HasInput + HasOutput + 'static,
+    T: HasInput + HasOutput + HasAbort + HasWasi + Send + 'static,
 {
     linker.func_wrap("wotto", "output", output)?;
     linker.func_wrap("wotto", "input", input)?;
@@ -60,5 +59,9 @@ where
         linker.func_wrap("env", "abort", env_abort)?;
     }
 
+    // Always linked: see the `crate::wasi` module doc comment on why per-module WASI
+    // gating happens at instantiation time in `crate::service`, not here.
+    wasmtime_wasi::preview1::add_to_linker_async(linker, HasWasi::wasi_ctx)?;
+
     Ok(())
 }
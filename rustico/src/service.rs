@@ -1,16 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use futures::future::AbortHandle;
 use thiserror::Error;
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
 use tracing::info;
 use wasmtime::*;
 
+use crate::capabilities::Capabilities;
+use crate::compiled_cache::CompiledModuleCache;
+use crate::config::{InstanceAllocation, ServiceConfig};
+use crate::lockfile::{self, LockEntry, Lockfile};
 use crate::registry::Registry;
-use crate::webload::{Domain, InvalidUrl, ResolvedModule, WebError};
-use crate::{runtime as rt, webload};
+use crate::wasi::{self, WasiState};
+use crate::webload::{Domain, ImportMap, InvalidUrl, ManifestError, ModuleManifest, ResolvedModule, WebError};
+use crate::{runtime as rt, watcher, webload};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -36,11 +43,73 @@ pub enum Error {
     CannotFetch(#[from] WebError),
     #[error("module {0} previously at url {1} not found")]
     ModuleGone(String, url::Url),
+    #[error("module {fqn} declares an incompatible manifest version {declared}")]
+    IncompatibleManifest { fqn: String, declared: String },
+    #[error("module {fqn} requires host capability {capability:?}, which this build does not provide")]
+    UnsupportedCapability { fqn: String, capability: String },
+    #[error("module manifest is invalid: {0}")]
+    InvalidManifest(#[from] ManifestError),
+    #[error("module {module} does not match its locked hash (expected {expected}, got {got})")]
+    IntegrityMismatch {
+        module: String,
+        expected: String,
+        got: String,
+    },
+    #[error("failed to persist lockfile: {0}")]
+    LockfileIo(#[from] std::io::Error),
+    #[error("module {0} is not pinned in the lockfile and the service is running frozen")]
+    NotPinned(String),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("failed to prepare git checkout: {0}")]
+    GitCheckoutIo(std::io::Error),
+    #[error("module imports WASI but its capability policy doesn't allow it")]
+    WasiNotAllowed,
+    #[error("entry point {entry_point} expects {expected} argument(s), got {got}")]
+    ArgumentCountMismatch {
+        entry_point: String,
+        expected: usize,
+        got: usize,
+    },
+    #[error("argument {0:?} is not valid for its parameter type")]
+    InvalidArgument(String),
+    #[error("entry point parameter/return type is not one of i32/i64/f32/f64")]
+    UnsupportedValueType,
+    #[error("execution ran out of fuel")]
+    OutOfFuel,
 }
 
+/// Directory that "builtin" (path-loaded) modules are resolved against, and
+/// the root watched by [`Command::Watch`] for hot reload.
+const MODULES_PATH: &str = "examples";
+
+/// How much fuel a guest may burn between yields back to the async runtime,
+/// so a compute-bound module doesn't monopolize a worker thread until its
+/// whole budget (or the epoch deadline) is spent.
+const FUEL_YIELD_INTERVAL: u64 = 10_000;
+
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 pub(crate) type WResult<T> = std::result::Result<T, anyhow::Error>;
 
+/// Named host interface capabilities a module's manifest may declare it
+/// requires. Checked at load time against [`ModuleManifest::capabilities`]
+/// so a module built against a host feature this build doesn't have is
+/// refused up front, instead of trapping obscurely the first time
+/// `run_module` reaches the missing import.
+const HOST_CAPABILITIES: &[&str] = &["output", "input", "wasi"];
+
+lazy_static::lazy_static! {
+    /// Range of module manifest versions this host is compatible with.
+    static ref HOST_PROTOCOL_VERSION: semver::VersionReq = semver::VersionReq::parse("^1").unwrap();
+    /// Compiled `wasmtime::Module`s keyed by the SHA-256 digest of the wasm
+    /// bytes they were compiled from, à la Deno's `CompiledWasmModuleStore`:
+    /// the same bytes resolved under a different fqn reuse the compiled
+    /// artifact instead of paying to recompile. This map lives only as long
+    /// as the process; a miss falls through to the on-disk
+    /// [`CompiledModuleCache`], which is what survives a restart.
+    static ref COMPILED_MODULES: Registry<String, Module> = Registry::default();
+}
+
 #[derive(Debug)]
 pub enum Command {
     LoadModule(String),
@@ -49,45 +118,277 @@ pub enum Command {
         entry_point: String,
         args: String,
     },
+    /// Start (`true`) or stop (`false`) the background filesystem watcher
+    /// that hot-reloads path-loaded modules on change.
+    Watch(bool),
+    /// Run `module`'s exported test functions, restricted to those whose
+    /// name matches `filter` (a single-`*`-glob, defaulting to `test_*`).
+    TestModule {
+        module: String,
+        filter: Option<String>,
+    },
+    /// Override `module`'s capability policy: `sandboxed` selects
+    /// [`Capabilities::sandboxed`], otherwise [`Capabilities::builtin`].
+    SetModulePolicy { module: String, sandboxed: bool },
     Quit,
     Idle,
 }
 
+/// The outcome of running a single test function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestOutcome {
+    Pass,
+    Fail,
+}
+
+/// One entry in the report returned by [`Service::test_module`].
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration: std::time::Duration,
+    pub message: Option<String>,
+}
+
+impl std::fmt::Display for TestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.outcome {
+            TestOutcome::Pass => "ok",
+            TestOutcome::Fail => "FAILED",
+        };
+        write!(f, "{status} {} ({:?})", self.name, self.duration)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A module's declared host-ABI version and required capabilities, as read
+/// from its `wotto-manifest` custom section. Returned by
+/// [`Service::module_info`] for diagnostics; see the `modinfo` command in
+/// `rusto-irc`.
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    /// `None` if the module declares no `wotto-manifest` section at all
+    /// (it predates this mechanism, or doesn't care to opt in).
+    pub version: Option<String>,
+    pub capabilities: Vec<String>,
+}
+
+/// What changed in [`Service::rescan_modules`]'s most recent pass over
+/// [`MODULES_PATH`]: fully-qualified names of modules it found and loaded
+/// for the first time, modules it already knew that it recompiled anyway
+/// (mirroring what [`watcher::spawn`]'s live reload would have done), and
+/// modules it used to know whose file is no longer on disk.
+#[derive(Debug, Clone, Default)]
+pub struct RescanReport {
+    pub added: Vec<String>,
+    pub reloaded: Vec<String>,
+    pub dropped: Vec<String>,
+}
+
 pub struct Service {
     engine: Engine,
     modules: Arc<Mutex<HashMap<String, Module>>>,
+    module_paths: Arc<Mutex<HashMap<String, PathBuf>>>,
+    /// Each loaded module's parsed manifest, if it declared one; consulted
+    /// by [`Service::module_info`]. Keyed the same as `modules`.
+    module_manifests: Mutex<HashMap<String, Option<ModuleManifest>>>,
     linker: Linker<RuntimeData>,
-    registry: Registry<String>,
+    registry: Registry<String, ResolvedModule>,
+    lockfile: Lockfile,
+    /// When set, [`Service::load_module`]/[`Service::load_module_from_url`]
+    /// refuse to fetch any module whose fqn has no entry in the lockfile
+    /// yet, instead of pinning it on the spot. See
+    /// [`Service::with_frozen_lockfile`].
+    frozen: bool,
+    watch_handle: Mutex<Option<JoinHandle<()>>>,
+    default_policy: Capabilities,
+    policies: Mutex<HashMap<String, Capabilities>>,
+    /// Abort handles for web fetches currently in flight, keyed by source
+    /// url, so [`Service::cancel_load`] can cut one short instead of
+    /// waiting out a hung origin. Entries are removed once their fetch
+    /// finishes, however it finishes.
+    in_flight_loads: Mutex<HashMap<String, AbortHandle>>,
+    /// Rewrites bare specifiers and aliases to trusted urls before a web
+    /// load's origin is checked. See [`Service::load_module_from_specifier`].
+    import_map: Mutex<ImportMap>,
+    /// On-disk cache of precompiled modules, keyed by content digest, so a
+    /// reload of unchanged bytes (or a restart) skips Cranelift entirely.
+    compiled_cache: CompiledModuleCache,
 }
 
-fn make_engine() -> Engine {
-    let mut config = Config::new();
-    config
-        .debug_info(true)
-        .wasm_backtrace_details(WasmBacktraceDetails::Enable)
-        .async_support(true)
-        .epoch_interruption(true)
-        .cranelift_opt_level(OptLevel::Speed);
-
-    Engine::new(&config).unwrap()
+fn make_engine(config: &ServiceConfig) -> Engine {
+    match Engine::new(&config.to_wasmtime_config()) {
+        Ok(engine) => engine,
+        // The pooling allocator reserves its whole pool of virtual address
+        // space up front; some sandboxes/containers refuse that reservation
+        // even though the host has plenty of actual memory. Fall back to
+        // wasmtime's default on-demand allocator rather than failing to
+        // start.
+        Err(err) if matches!(config.instance_allocation, InstanceAllocation::Pooling(_)) => {
+            tracing::warn!(
+                "pooling instance allocator unavailable ({err:#}), falling back to on-demand allocation"
+            );
+            let mut fallback = config.clone();
+            fallback.instance_allocation = InstanceAllocation::OnDemand;
+            Engine::new(&fallback.to_wasmtime_config())
+                .expect("on-demand allocation should always be available")
+        }
+        Err(err) => panic!("failed to create wasmtime engine: {err:#}"),
+    }
 }
 
 impl Service {
     pub fn new() -> Self {
-        let engine = make_engine();
+        Self::with_config(ServiceConfig::default())
+    }
+
+    /// Build a `Service` whose wasmtime `Engine` is configured per
+    /// `config`, e.g. to enable JIT profiling for `perf`/VTune.
+    pub fn with_config(config: ServiceConfig) -> Self {
+        let engine = make_engine(&config);
         let mut linker = Linker::new(&engine);
         rt::add_to_linker(&mut linker, true)
             .map_err(Error::Wasm)
             .expect("runtime linking should be possible without shadowing");
 
+        let compiled_cache = CompiledModuleCache::open_default(&engine)
+            .expect("on-disk compiled-module cache directory should be creatable");
+
         Service {
             engine,
             modules: Arc::new(HashMap::new().into()),
+            module_paths: Arc::new(HashMap::new().into()),
+            module_manifests: Mutex::new(HashMap::new()),
             linker,
             registry: Registry::default(),
+            lockfile: Lockfile::default(),
+            frozen: false,
+            watch_handle: Mutex::new(None),
+            default_policy: Capabilities::default(),
+            policies: Mutex::new(HashMap::new()),
+            in_flight_loads: Mutex::new(HashMap::new()),
+            import_map: Mutex::new(ImportMap::new()),
+            compiled_cache,
+        }
+    }
+
+    /// Refuse to fetch any web module not already recorded in the lockfile,
+    /// à la Deno's `--frozen-lockfile`: a module that was never loaded
+    /// before is rejected with [`Error::NotPinned`] instead of being
+    /// fetched and pinned on the spot, so a deployed set of modules can
+    /// only ever shrink to what the lockfile already names, never grow.
+    pub fn with_frozen_lockfile(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+
+    /// Override the policy applied to modules with no more specific
+    /// [`Service::set_module_policy`] override.
+    pub fn with_default_policy(mut self, policy: Capabilities) -> Self {
+        self.default_policy = policy;
+        self
+    }
+
+    /// Install a per-module capability override, consulted ahead of the
+    /// default policy the next time `module_name` is run.
+    pub async fn set_module_policy(&self, module_name: impl Into<String>, policy: Capabilities) {
+        self.policies.lock().await.insert(module_name.into(), policy);
+    }
+
+    /// The capabilities that apply to `module_name`: its own override if
+    /// one was set, otherwise the default policy.
+    async fn capabilities_for(&self, module_name: &str) -> Capabilities {
+        self.policies
+            .lock()
+            .await
+            .get(module_name)
+            .cloned()
+            .unwrap_or_else(|| self.default_policy.clone())
+    }
+
+    /// The host-ABI version and capabilities `module_name` declared in its
+    /// manifest, for diagnosing a load failure without reading logs. Errs
+    /// with [`Error::ModuleNotFound`] if no such module is loaded; a loaded
+    /// module that declares no `wotto-manifest` section reports `version:
+    /// None` and no capabilities, rather than being treated as not found.
+    pub async fn module_info(&self, module_name: &str) -> Result<ModuleInfo> {
+        if !self.modules.lock().await.contains_key(module_name) {
+            return Err(Error::ModuleNotFound);
+        }
+        let manifest = self.module_manifests.lock().await.get(module_name).cloned().flatten();
+        Ok(match manifest {
+            Some(manifest) => ModuleInfo {
+                version: manifest.version().ok().map(|version| version.to_string()),
+                capabilities: manifest.capabilities().iter().cloned().collect(),
+            },
+            None => ModuleInfo {
+                version: None,
+                capabilities: Vec::new(),
+            },
+        })
+    }
+
+    /// Trust `public_key` (a raw 32-byte Ed25519 key) to sign modules
+    /// published as `user` on `domain`. Once at least one key is trusted for
+    /// a domain/user, every module loaded from it must carry a valid,
+    /// matching detached signature or the load is rejected.
+    pub(crate) fn trust_signing_key(
+        &self,
+        domain: Domain,
+        user: impl Into<String>,
+        key_id: impl Into<String>,
+        public_key: &[u8; 32],
+    ) -> Result<()> {
+        webload::trust_key(domain, user, key_id, public_key)
+    }
+
+    /// Trust `username`/`password` to answer HTTP Basic/Digest auth
+    /// challenges from `origin`, e.g. for an internal mirror or private gist
+    /// host behind a 401. A host never receives credentials unless it's
+    /// trusted this way, no matter what a challenge asks for.
+    pub(crate) fn trust_origin_credentials(
+        &self,
+        origin: url::Origin,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) {
+        webload::trust_credentials(origin, username, password)
+    }
+
+    /// Cancel the web fetch currently in flight for `url`, if any. Returns
+    /// whether there was one to cancel; a url that isn't (or is no longer)
+    /// loading is a no-op, not an error, so callers don't need to race
+    /// against completion.
+    pub async fn cancel_load(&self, url: &str) -> bool {
+        match self.in_flight_loads.lock().await.get(url) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
         }
     }
 
+    /// Begins tracking a fetch for `url` under a fresh [`AbortHandle`] so
+    /// [`Service::cancel_load`] can cut it short, returning the matching
+    /// [`AbortRegistration`] half to pass to the cancellable fetch call.
+    /// Pair with [`Self::end_tracked_fetch`] once the fetch is done,
+    /// however it ends.
+    async fn begin_tracked_fetch(&self, url: &url::Url) -> futures::future::AbortRegistration {
+        let (handle, registration) = AbortHandle::new_pair();
+        self.in_flight_loads.lock().await.insert(url.to_string(), handle);
+        registration
+    }
+
+    /// Stops tracking the fetch started by [`Self::begin_tracked_fetch`] for
+    /// `url`.
+    async fn end_tracked_fetch(&self, url: &url::Url) {
+        self.in_flight_loads.lock().await.remove(&url.to_string());
+    }
+
     pub fn increment_epoch(&self) {
         self.engine.increment_epoch();
     }
@@ -96,12 +397,26 @@ impl Service {
         // used for manual testing, maybe deprecate?
         while let Some(cmd) = rx.recv().await {
             let result = match cmd {
-                Command::LoadModule(name) => self.load_module(name).await,
+                Command::LoadModule(name) => self.load_module(name, false).await,
                 Command::RunModule {
                     module,
                     entry_point,
                     args,
                 } => self.run_module(&module, &entry_point, &args).await,
+                Command::Watch(enable) => self.set_watching(enable).await,
+                Command::TestModule { module, filter } => self
+                    .test_module(&module, filter)
+                    .await
+                    .map(|report| format_test_report(&report)),
+                Command::SetModulePolicy { module, sandboxed } => {
+                    let policy = if sandboxed {
+                        Capabilities::sandboxed()
+                    } else {
+                        Capabilities::builtin()
+                    };
+                    self.set_module_policy(module.clone(), policy).await;
+                    Ok(format!("policy updated for {module}"))
+                }
                 Command::Idle => {
                     continue;
                 }
@@ -120,8 +435,42 @@ impl Service {
         modules.insert(fqn, module);
     }
 
+    /// Validates `bytes` as a wasm module, writes it into the
+    /// content-addressed store under [`MODULES_PATH`] (keyed by its SHA-256
+    /// digest, same hash algorithm as the lockfile), and returns that digest
+    /// as the module's stored id. If `name` is given and `auto_load` is
+    /// true, the bytes are also written under `name`'s file name (as given,
+    /// with no extension added) and loaded under `name` exactly as
+    /// [`Service::load_module`] would, so it's callable
+    /// immediately; otherwise the upload is just kept in the store, address-
+    /// able by its digest, for a later explicit `load_module`.
+    #[tracing::instrument(skip(self, bytes))]
+    pub async fn upload_module(&self, bytes: Vec<u8>, name: Option<String>, auto_load: bool) -> Result<String> {
+        // reject unparseable uploads before they ever touch the store.
+        Module::new(&self.engine, &bytes).map_err(Error::Wasm)?;
+        let hash = lockfile::digest(&bytes);
+        let stored_path = Path::new(MODULES_PATH).join(format!("{hash}.wasm"));
+        std::fs::write(&stored_path, &bytes).map_err(|error| Error::Wasm(error.into()))?;
+
+        if let Some(name) = name {
+            if auto_load {
+                let name_as_path = PathBuf::from_str(&name).map_err(|_| Error::InvalidModuleName)?;
+                let file_name = name_as_path.file_name().ok_or(Error::InvalidModuleName)?;
+                let named_path = Path::new(MODULES_PATH).join(file_name);
+                std::fs::write(&named_path, &bytes).map_err(|error| Error::Wasm(error.into()))?;
+                self.load_module(name, false).await?;
+            }
+        }
+        Ok(hash)
+    }
+
+    /// Load (or reload) the module named `name`. On a reload, `trust_new_hash`
+    /// controls what happens if the freshly fetched bytes no longer match the
+    /// hash recorded in the lockfile: when `false`, the reload is refused
+    /// with [`Error::IntegrityMismatch`]; when `true`, the new hash is
+    /// accepted and replaces the locked one.
     #[tracing::instrument(skip(self))]
-    pub async fn load_module(&self, name: String) -> Result<String> {
+    pub async fn load_module(&self, name: String, trust_new_hash: bool) -> Result<String> {
         let mut entry = self.registry.lock_entry(name.clone()).await;
         if let Some(webmodule) = &mut *entry {
             let url = webmodule.url().clone();
@@ -130,7 +479,10 @@ impl Service {
             // when the user requests re-resolution (e.g. same URL gives a
             // newer version) vs when we want to just attempt a reload?
             // unsure if the "just reload" case actually exists
-            let new_webmodule = webload::resolve(url.clone()).await?;
+            let registration = self.begin_tracked_fetch(&url).await;
+            let new_webmodule = webload::resolve_cancellable(url.clone(), Some(registration)).await;
+            self.end_tracked_fetch(&url).await;
+            let new_webmodule = new_webmodule?;
             let new_fqn = self.fqn_for_module(webmodule);
             if new_fqn != name {
                 // a given url used to provide a module name, but it
@@ -139,38 +491,199 @@ impl Service {
                 // mess with the registry state here.
                 return Err(Error::ModuleGone(name, url));
             }
-            self.load_web_module_with_lock(&mut entry, name.clone(), new_webmodule)
+            self.load_web_module_with_lock(&mut entry, name.clone(), new_webmodule, trust_new_hash)
                 .await?;
             return Ok(name);
         }
         // quick and dirty name validation + path loading
-        const MODULES_PATH: &str = "examples";
         let name_as_path = PathBuf::from_str(&name).map_err(|_| Error::InvalidModuleName)?;
         let file_name = name_as_path.file_name().ok_or(Error::InvalidModuleName)?;
         let path = Path::new(MODULES_PATH).join(file_name);
         // "builtin" modules have a short fqn with no namespace or prefix
         // TODO: unify the builtin and web code paths
         let fqn = canonicalize_name(&path)?;
-        let module = Module::from_file(&self.engine, &path).map_err(Error::Wasm)?;
+        let bytes = std::fs::read(&path).map_err(|error| Error::Wasm(error.into()))?;
+        let manifest = webload::parse_manifest(&bytes)?;
+        if let Some(manifest) = &manifest {
+            check_manifest_compat(&fqn, manifest)?;
+        }
+        let module = Module::new(&self.engine, &bytes).map_err(Error::Wasm)?;
         self.add_module(fqn.clone(), module).await;
+        self.module_paths.lock().await.insert(fqn.clone(), path);
+        self.module_manifests.lock().await.insert(fqn.clone(), manifest);
         Ok(fqn)
     }
 
+    /// Start or stop the background filesystem watcher that hot-reloads
+    /// path-loaded modules under [`MODULES_PATH`] as their source files
+    /// change. A module that fails to recompile keeps its previously-good
+    /// version live rather than being removed from the map.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_watching(&self, enable: bool) -> Result<String> {
+        let mut handle = self.watch_handle.lock().await;
+        if enable {
+            if handle.is_some() {
+                return Ok("already watching".to_string());
+            }
+            *handle = Some(watcher::spawn(
+                self.engine.clone(),
+                self.modules.clone(),
+                self.module_paths.clone(),
+                vec![PathBuf::from(MODULES_PATH)],
+            ));
+            info!(path = MODULES_PATH, "started filesystem watcher");
+            Ok("watching".to_string())
+        } else {
+            if let Some(task) = handle.take() {
+                task.abort();
+                info!("stopped filesystem watcher");
+            }
+            Ok("not watching".to_string())
+        }
+    }
+
+    /// Rescans [`MODULES_PATH`] on demand instead of waiting for the next
+    /// filesystem event: every `.wasm` file not already registered is
+    /// loaded fresh, every already-registered path-loaded module is
+    /// recompiled from its current bytes (the same thing a `Modify` event
+    /// would trigger under [`Service::set_watching`]), and any registered
+    /// module whose file is no longer on disk is dropped. A module that
+    /// fails to parse its manifest or compile is logged and left out of the
+    /// report rather than failing the whole rescan. A file whose fqn
+    /// collides with an already-registered web-loaded module (see
+    /// [`Registry`]) is skipped rather than overwriting it, since
+    /// `load_module` always treats the registry as authoritative for that
+    /// name. Backs `POST /modules/reload` in `rusto-irc`.
+    #[tracing::instrument(skip(self))]
+    pub async fn rescan_modules(&self) -> RescanReport {
+        let mut report = RescanReport::default();
+        let entries = match std::fs::read_dir(MODULES_PATH) {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::error!(path = MODULES_PATH, %error, "failed to scan modules directory");
+                return report;
+            }
+        };
+
+        let mut seen_paths = HashSet::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("wasm") {
+                continue;
+            }
+            seen_paths.insert(path.clone());
+
+            let Ok(fqn) = canonicalize_name(&path) else { continue };
+            if self.registry.lock_entry(fqn.clone()).await.is_some() {
+                // a web-loaded module already owns this fqn; a same-named
+                // file under MODULES_PATH doesn't get to shadow it, and the
+                // registry (not self.modules) is the source of truth for web
+                // modules, so just leave it alone.
+                tracing::warn!(module = fqn, path = %path.display(), "skipping rescan of file shadowing a web-loaded module");
+                continue;
+            }
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    tracing::error!(path = %path.display(), %error, "failed to read module during rescan");
+                    continue;
+                }
+            };
+            let manifest = match webload::parse_manifest(&bytes) {
+                Ok(manifest) => manifest,
+                Err(error) => {
+                    tracing::error!(module = fqn, %error, "invalid manifest during rescan");
+                    continue;
+                }
+            };
+            if let Some(manifest) = &manifest {
+                if let Err(error) = check_manifest_compat(&fqn, manifest) {
+                    tracing::error!(module = fqn, %error, "rejecting module during rescan");
+                    continue;
+                }
+            }
+            let module = match Module::new(&self.engine, &bytes) {
+                Ok(module) => module,
+                Err(error) => {
+                    tracing::error!(module = fqn, %error, "failed to compile module during rescan");
+                    continue;
+                }
+            };
+
+            let was_loaded = {
+                let mut modules = self.modules.lock().await;
+                modules.insert(fqn.clone(), module).is_some()
+            };
+            self.module_paths.lock().await.insert(fqn.clone(), path);
+            self.module_manifests.lock().await.insert(fqn.clone(), manifest);
+            if was_loaded {
+                report.reloaded.push(fqn);
+            } else {
+                report.added.push(fqn);
+            }
+        }
+
+        let gone: Vec<String> = self
+            .module_paths
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, path)| !seen_paths.contains(*path))
+            .map(|(fqn, _)| fqn.clone())
+            .collect();
+        for fqn in gone {
+            self.modules.lock().await.remove(&fqn);
+            self.module_paths.lock().await.remove(&fqn);
+            self.module_manifests.lock().await.remove(&fqn);
+            report.dropped.push(fqn);
+        }
+
+        report
+    }
+
+    /// Register an import-map rewrite: `specifier` (an exact alias, or a
+    /// `/`-suffixed prefix) resolves to `target` instead of being parsed as
+    /// a url directly. See [`Service::load_module_from_specifier`].
+    pub async fn set_import(&self, specifier: impl Into<String>, target: impl Into<String>) {
+        self.import_map.lock().await.insert(specifier, target);
+    }
+
+    /// Load a module from a bare specifier or alias (e.g. `"chatbot"`)
+    /// instead of a literal url: rewritten through the configured import
+    /// map first, then loaded exactly as [`Service::load_module_from_url`]
+    /// would, with the origin allow-list still the final gate on where the
+    /// rewritten url is actually allowed to point.
+    #[tracing::instrument(skip(self))]
+    pub async fn load_module_from_specifier(&self, specifier: &str) -> Result<String> {
+        let url = self.import_map.lock().await.resolve(specifier, None)?;
+        self.load_module_from_url(url.as_str()).await
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn load_module_from_url(&self, url: &str) -> Result<String> {
         let url: url::Url = url.parse().map_err(|_| InvalidUrl::ParseError)?;
-        let webmodule = webload::resolve(url).await?;
+        let registration = self.begin_tracked_fetch(&url).await;
+        let webmodule = webload::resolve_cancellable(url.clone(), Some(registration)).await;
+        self.end_tracked_fetch(&url).await;
+        let webmodule = webmodule?;
         // the content might or might not be loaded at this point, but we have
         // enough information to determine the name of the module
         let fqn = self.fqn_for_module(&webmodule);
-        self.load_web_module(fqn.clone(), webmodule).await?;
+        // not a reload, so a hash already locked under this fqn (e.g. by an
+        // earlier run) is never replaced here: differing bytes are refused
+        self.load_web_module(fqn.clone(), webmodule, false).await?;
         Ok(fqn)
     }
 
-    #[tracing::instrument(skip(self))]
-    async fn load_web_module(&self, fqn: String, webmodule: ResolvedModule) -> Result<()> {
+    #[tracing::instrument(skip(self, webmodule))]
+    async fn load_web_module(
+        &self,
+        fqn: String,
+        webmodule: ResolvedModule,
+        trust_new_hash: bool,
+    ) -> Result<()> {
         let mut entry = self.registry.lock_entry(fqn.clone()).await;
-        self.load_web_module_with_lock(&mut entry, fqn, webmodule)
+        self.load_web_module_with_lock(&mut entry, fqn, webmodule, trust_new_hash)
             .await
     }
 
@@ -179,13 +692,62 @@ impl Service {
         entry: &'a mut Option<ResolvedModule>,
         fqn: String,
         mut webmodule: ResolvedModule,
+        trust_new_hash: bool,
     ) -> Result<()> {
-        webmodule.ensure_content().await?;
+        if self.frozen && self.lockfile.get(&fqn).await.is_none() {
+            return Err(Error::NotPinned(fqn));
+        }
+        let url = webmodule.url().clone();
+        let registration = self.begin_tracked_fetch(&url).await;
+        let fetched = webmodule.ensure_content_cancellable(Some(registration)).await;
+        self.end_tracked_fetch(&url).await;
+        fetched?;
+        let manifest = webmodule.manifest()?;
+        if let Some(manifest) = &manifest {
+            check_manifest_compat(&fqn, manifest)?;
+        }
+        self.module_manifests.lock().await.insert(fqn.clone(), manifest);
         let bytes = webmodule
             .content()
             .expect("loaded module should already have content");
-        let wasm_module = Module::new(&self.engine, bytes).map_err(Error::Wasm)?;
+
+        let hash = lockfile::digest(bytes);
+        if let Some(locked) = self.lockfile.get(&fqn).await {
+            if locked.hash != hash && !trust_new_hash {
+                return Err(Error::IntegrityMismatch {
+                    module: fqn,
+                    expected: locked.hash,
+                    got: hash,
+                });
+            }
+        }
+        self.lockfile
+            .record(
+                fqn.clone(),
+                LockEntry {
+                    url: webmodule.url().to_string(),
+                    hash: hash.clone(),
+                },
+            )
+            .await?;
+
+        let engine = self.engine.clone();
+        let compiled_cache = &self.compiled_cache;
+        let bytes = bytes.to_vec();
+        let digest = hash.clone();
+        let wasm_module = COMPILED_MODULES
+            .get_or_try_insert_with(hash, move || async move {
+                compiled_cache.load_or_compile(&engine, &digest, &bytes)
+            })
+            .await?;
         self.add_module(fqn.clone(), wasm_module).await;
+        // web-loaded modules are untrusted by default; don't clobber an
+        // operator-set override on reload.
+        self.policies
+            .lock()
+            .await
+            .entry(fqn.clone())
+            .or_insert_with(Capabilities::sandboxed);
         *entry = Some(webmodule);
         Ok(())
     }
@@ -195,6 +757,7 @@ impl Service {
         let user = webmodule.user();
         let namespace = match webmodule.domain() {
             Domain::Github => Some(user.to_string()),
+            Domain::Git => Some(user.to_string()),
             Domain::Builtin => None,
             Domain::Other(domain) => Some(format!("{user}@{domain}")),
         };
@@ -206,7 +769,7 @@ impl Service {
         }
     }
 
-    #[tracing::instrument(skip(self))]
+    #[tracing::instrument(skip(self), fields(fuel_consumed = tracing::field::Empty))]
     pub async fn run_module(
         &self,
         module_name: &str,
@@ -221,10 +784,20 @@ impl Service {
                 .clone()
         };
 
-        let runtime_data = RuntimeData::new(args.to_string(), 512);
+        let capabilities = self.capabilities_for(module_name).await;
+        if requires_wasi(&module) && !capabilities.allow_wasi {
+            return Err(Error::WasiNotAllowed);
+        }
+        let duration = capabilities.max_wall_time;
+        let fuel_budget = capabilities.max_fuel;
+        let runtime_data = RuntimeData::new(args.to_string(), capabilities);
         let mut store = Store::new(&self.engine, runtime_data);
         store.limiter(|state| &mut state.limits);
         store.epoch_deadline_async_yield_and_update(1);
+        store.set_fuel(fuel_budget).map_err(Error::Wasm)?;
+        store
+            .fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))
+            .map_err(Error::Wasm)?;
 
         let instance = self
             .linker
@@ -235,26 +808,229 @@ impl Service {
         let func = instance
             .get_func(&mut store, entry_point)
             .ok_or(Error::FunctionNotFound)?;
-        let tyfunc = func
-            .typed::<(), ()>(&mut store)
-            .map_err(|_| Error::WrongFunctionType)?;
-
-        let duration = std::time::Duration::from_millis(5000);
-        let fut = tyfunc.call_async(&mut store, ());
-        match tokio::time::timeout(duration, fut).await {
-            Ok(Ok(())) => {}
-            Ok(Err(err)) => {
-                return Err(Error::Wasm(err));
-            }
-            Err(_) => {
-                return Err(Error::TimedOut);
+        let func_ty = func.ty(&store);
+        let param_types: Vec<ValType> = func_ty.params().collect();
+        let result_types: Vec<ValType> = func_ty.results().collect();
+
+        // No parameters and no return value: the original `()->()` path,
+        // unchanged, so existing modules (which take their input through
+        // `wotto.input` instead) keep working exactly as before.
+        if param_types.is_empty() && result_types.is_empty() {
+            let tyfunc = func
+                .typed::<(), ()>(&mut store)
+                .map_err(|_| Error::WrongFunctionType)?;
+            run_with_timeout(duration, tyfunc.call_async(&mut store, ())).await?;
+            drain_wasi_output(&mut store)?;
+            record_fuel_consumption(&store, fuel_budget);
+            return Ok(store.into_data().output);
+        }
+
+        let tokens = split_args(args);
+        if tokens.len() != param_types.len() {
+            return Err(Error::ArgumentCountMismatch {
+                entry_point: entry_point.to_string(),
+                expected: param_types.len(),
+                got: tokens.len(),
+            });
+        }
+        let arg_vals = param_types
+            .iter()
+            .zip(tokens)
+            .map(|(ty, token)| parse_val(ty, token))
+            .collect::<Result<Vec<_>>>()?;
+        let mut results = vec![Val::I32(0); result_types.len()];
+        run_with_timeout(
+            duration,
+            func.call_async(&mut store, &arg_vals, &mut results),
+        )
+        .await?;
+        drain_wasi_output(&mut store)?;
+        record_fuel_consumption(&store, fuel_budget);
+
+        if results.is_empty() {
+            Ok(store.into_data().output)
+        } else {
+            results.iter().map(format_val).collect::<Result<Vec<_>>>().map(|parts| parts.join(" "))
+        }
+    }
+
+    /// Discover and run `module_name`'s exported test functions, i.e. those
+    /// whose name matches `filter` (default `test_*`) and that type-check as
+    /// `fn()`. Each test runs in its own fresh [`Store`], so one test's
+    /// state (and a trap in one) never leaks into another.
+    #[tracing::instrument(skip(self))]
+    pub async fn test_module(
+        &self,
+        module_name: &str,
+        filter: Option<String>,
+    ) -> Result<Vec<TestResult>> {
+        let module = {
+            let modules = self.modules.lock().await;
+            modules
+                .get(module_name)
+                .ok_or(Error::ModuleNotFound)?
+                .clone()
+        };
+        let filter = filter.unwrap_or_else(|| "test_*".to_string());
+        let capabilities = self.capabilities_for(module_name).await;
+        if requires_wasi(&module) && !capabilities.allow_wasi {
+            return Err(Error::WasiNotAllowed);
+        }
+        let names = self.discover_tests(&module, &filter, &capabilities).await?;
+
+        let mut report = Vec::with_capacity(names.len());
+        for name in names {
+            report.push(self.run_test(&module, name, &capabilities).await);
+        }
+        Ok(report)
+    }
+
+    /// List the exported functions of `module` that match `filter` and
+    /// type-check as `fn()`, using a throwaway instance purely for
+    /// discovery.
+    async fn discover_tests(
+        &self,
+        module: &Module,
+        filter: &str,
+        capabilities: &Capabilities,
+    ) -> Result<Vec<String>> {
+        let runtime_data = RuntimeData::new(String::new(), capabilities.clone());
+        let mut store = Store::new(&self.engine, runtime_data);
+        store.limiter(|state| &mut state.limits);
+        store.epoch_deadline_async_yield_and_update(1);
+        store.set_fuel(capabilities.max_fuel).map_err(Error::Wasm)?;
+        store
+            .fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))
+            .map_err(Error::Wasm)?;
+
+        let instance = self
+            .linker
+            .instantiate_async(&mut store, module)
+            .await
+            .map_err(Error::Wasm)?;
+        let export_names: Vec<String> = instance
+            .exports(&mut store)
+            .map(|export| export.name().to_string())
+            .filter(|name| matches_test_filter(name, filter))
+            .collect();
+
+        let mut names = Vec::with_capacity(export_names.len());
+        for name in export_names {
+            let Some(func) = instance.get_func(&mut store, &name) else {
+                continue;
+            };
+            if func.typed::<(), ()>(&store).is_ok() {
+                names.push(name);
             }
         }
+        Ok(names)
+    }
+
+    /// Run a single test function in a fresh store, capturing a trap or
+    /// timeout as a failure and any `env.abort` message as the failure text.
+    async fn run_test(&self, module: &Module, name: String, capabilities: &Capabilities) -> TestResult {
+        let runtime_data = RuntimeData::new(String::new(), capabilities.clone());
+        let mut store = Store::new(&self.engine, runtime_data);
+        store.limiter(|state| &mut state.limits);
+        store.epoch_deadline_async_yield_and_update(1);
+        if let Err(err) = store.set_fuel(capabilities.max_fuel) {
+            return TestResult {
+                name,
+                outcome: TestOutcome::Fail,
+                duration: std::time::Duration::ZERO,
+                message: Some(Error::Wasm(err).to_string()),
+            };
+        }
+        if let Err(err) = store.fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL)) {
+            return TestResult {
+                name,
+                outcome: TestOutcome::Fail,
+                duration: std::time::Duration::ZERO,
+                message: Some(Error::Wasm(err).to_string()),
+            };
+        }
+
+        let start = std::time::Instant::now();
+        let max_wall_time = capabilities.max_wall_time;
+        let trap_message = self
+            .run_test_body(&mut store, module, &name, max_wall_time)
+            .await
+            .err();
+        let duration = start.elapsed();
+        let abort_message = store.into_data().abort_message;
+
+        match trap_message {
+            None => TestResult {
+                name,
+                outcome: TestOutcome::Pass,
+                duration,
+                message: abort_message,
+            },
+            Some(trap_message) => TestResult {
+                name,
+                outcome: TestOutcome::Fail,
+                duration,
+                message: abort_message.or(Some(trap_message)),
+            },
+        }
+    }
+
+    async fn run_test_body(
+        &self,
+        store: &mut Store<RuntimeData>,
+        module: &Module,
+        name: &str,
+        max_wall_time: std::time::Duration,
+    ) -> std::result::Result<(), String> {
+        let instance = self
+            .linker
+            .instantiate_async(&mut *store, module)
+            .await
+            .map_err(|err| Error::Wasm(err).to_string())?;
+        let func = instance
+            .get_func(&mut *store, name)
+            .ok_or_else(|| Error::FunctionNotFound.to_string())?;
+        let typed = func
+            .typed::<(), ()>(&*store)
+            .map_err(|_| Error::WrongFunctionType.to_string())?;
+
+        let fut = typed.call_async(&mut *store, ());
+        match tokio::time::timeout(max_wall_time, fut).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(trap_to_error(err).to_string()),
+            Err(_) => Err(Error::TimedOut.to_string()),
+        }
+    }
+}
 
-        Ok(store.into_data().output)
+/// Matches `name` against a single-`*`-glob `pattern` (e.g. the default
+/// `test_*`): the part before `*` must prefix `name` and the part after
+/// must suffix it.
+fn matches_test_filter(name: &str, pattern: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => name == pattern,
     }
 }
 
+fn format_test_report(report: &[TestResult]) -> String {
+    let passed = report
+        .iter()
+        .filter(|result| result.outcome == TestOutcome::Pass)
+        .count();
+    let summary = format!("{passed}/{} tests passed", report.len());
+    report
+        .iter()
+        .map(|result| result.to_string())
+        .chain(std::iter::once(summary))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 impl Default for Service {
     fn default() -> Self {
         Self::new()
@@ -264,22 +1040,26 @@ impl Default for Service {
 struct RuntimeData {
     message: String,
     output: String,
-    capacity: usize,
     limits: StoreLimits,
+    abort_message: Option<String>,
+    capabilities: Capabilities,
+    wasi: WasiState,
 }
 
 impl RuntimeData {
-    fn new(message: String, output_capacity: usize) -> Self {
-        let output = String::with_capacity(output_capacity);
+    fn new(message: String, capabilities: Capabilities) -> Self {
         let limits = StoreLimitsBuilder::new()
-            .memory_size(1 << 20)
-            .table_elements(10 << 10)
+            .memory_size(capabilities.max_memory_bytes)
+            .table_elements(capabilities.max_table_elements)
             .build();
+        let wasi = wasi::build_state(&capabilities);
         Self {
             message,
-            output,
-            capacity: output_capacity,
+            output: String::new(),
             limits,
+            abort_message: None,
+            capabilities,
+            wasi,
         }
     }
 }
@@ -289,7 +1069,15 @@ pub(crate) trait HasInput {
 }
 
 pub(crate) trait HasOutput {
-    fn output(&mut self, text: &str);
+    fn output(&mut self, text: &str) -> WResult<()>;
+}
+
+pub(crate) trait HasAbort {
+    fn record_abort(&mut self, message: String);
+}
+
+pub(crate) trait HasWasi {
+    fn wasi_ctx(&mut self) -> &mut wasmtime_wasi::preview1::WasiP1Ctx;
 }
 
 impl HasInput for RuntimeData {
@@ -299,9 +1087,31 @@ impl HasInput for RuntimeData {
 }
 
 impl HasOutput for RuntimeData {
-    fn output(&mut self, text: &str) {
-        let Some(available) = self.capacity.checked_sub(self.output.len()) else { return; };
-        self.output += &text[..available.min(text.len())];
+    fn output(&mut self, text: &str) -> WResult<()> {
+        if !self.capabilities.allow_output {
+            return Err(Trap::Interrupt.into());
+        }
+        let available = self
+            .capabilities
+            .output_budget
+            .saturating_sub(self.output.len());
+        if text.len() > available {
+            return Err(Trap::Interrupt.into());
+        }
+        self.output += text;
+        Ok(())
+    }
+}
+
+impl HasAbort for RuntimeData {
+    fn record_abort(&mut self, message: String) {
+        self.abort_message = Some(message);
+    }
+}
+
+impl HasWasi for RuntimeData {
+    fn wasi_ctx(&mut self) -> &mut wasmtime_wasi::preview1::WasiP1Ctx {
+        &mut self.wasi.ctx
     }
 }
 
@@ -314,6 +1124,127 @@ pub(crate) fn get_memory<T>(caller: &mut Caller<'_, T>) -> Result<Memory> {
     Ok(mem)
 }
 
+/// Whether `module` imports anything under the `wasi_snapshot_preview1`
+/// namespace, i.e. whether it needs [`Capabilities::allow_wasi`] to run.
+fn requires_wasi(module: &Module) -> bool {
+    module
+        .imports()
+        .any(|import| import.module() == wasi::WASI_PREVIEW1_MODULE)
+}
+
+/// Move anything `store`'s module wrote through WASI stdout/stderr into
+/// `HasOutput`'s buffer, so a WASI module's output surfaces the same way
+/// `wotto.output` calls already do.
+fn drain_wasi_output(store: &mut Store<RuntimeData>) -> Result<()> {
+    let stdout = store.data().wasi.stdout.contents();
+    let stderr = store.data().wasi.stderr.contents();
+    let data = store.data_mut();
+    if !stdout.is_empty() {
+        data.output(&String::from_utf8_lossy(&stdout)).map_err(Error::Wasm)?;
+    }
+    if !stderr.is_empty() {
+        data.output(&String::from_utf8_lossy(&stderr)).map_err(Error::Wasm)?;
+    }
+    Ok(())
+}
+
+/// Await `fut`, translating a trap via [`trap_to_error`] (into
+/// [`Error::OutOfFuel`] or [`Error::Wasm`]) and an expired
+/// `duration` into [`Error::TimedOut`]. Shared between the no-argument and
+/// typed-argument call paths in [`Service::run_module`].
+async fn run_with_timeout<Fut>(duration: std::time::Duration, fut: Fut) -> Result<()>
+where
+    Fut: std::future::Future<Output = WResult<()>>,
+{
+    match tokio::time::timeout(duration, fut).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(err)) => Err(trap_to_error(err)),
+        Err(_) => Err(Error::TimedOut),
+    }
+}
+
+/// Distinguishes a fuel-exhaustion trap ([`Error::OutOfFuel`]) from any other
+/// wasm trap ([`Error::Wasm`]), so a tight compute loop that burns through
+/// its budget is reported distinctly from a generic runtime error.
+fn trap_to_error(err: anyhow::Error) -> Error {
+    match err.downcast_ref::<Trap>() {
+        Some(&Trap::OutOfFuel) => Error::OutOfFuel,
+        _ => Error::Wasm(err),
+    }
+}
+
+/// Records how much of `fuel_budget` a finished `run_module` call actually
+/// burned on the current tracing span, so operators can see how expensive an
+/// invocation was without changing `run_module`'s return type.
+fn record_fuel_consumption(store: &Store<RuntimeData>, fuel_budget: u64) {
+    let remaining = store.get_fuel().unwrap_or(0);
+    let consumed = fuel_budget.saturating_sub(remaining);
+    tracing::Span::current().record("fuel_consumed", consumed);
+}
+
+/// Splits `args` on commas and/or whitespace, dropping empty tokens, so
+/// `"1, 2"`, `"1 2"` and `"1,2"` are all accepted as two arguments.
+fn split_args(args: &str) -> Vec<&str> {
+    args.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Parses `token` as `ty`, the only numeric value types `wasm` functions
+/// can take as parameters/return directly.
+fn parse_val(ty: &ValType, token: &str) -> Result<Val> {
+    let invalid = || Error::InvalidArgument(token.to_string());
+    match ty {
+        ValType::I32 => token.parse::<i32>().map(Val::I32).map_err(|_| invalid()),
+        ValType::I64 => token.parse::<i64>().map(Val::I64).map_err(|_| invalid()),
+        ValType::F32 => token
+            .parse::<f32>()
+            .map(|v| Val::F32(v.to_bits()))
+            .map_err(|_| invalid()),
+        ValType::F64 => token
+            .parse::<f64>()
+            .map(|v| Val::F64(v.to_bits()))
+            .map_err(|_| invalid()),
+        _ => Err(Error::UnsupportedValueType),
+    }
+}
+
+/// Formats a result `Val` for the plain-text response `run_module` returns.
+fn format_val(val: &Val) -> Result<String> {
+    match val {
+        Val::I32(v) => Ok(v.to_string()),
+        Val::I64(v) => Ok(v.to_string()),
+        Val::F32(bits) => Ok(f32::from_bits(*bits).to_string()),
+        Val::F64(bits) => Ok(f64::from_bits(*bits).to_string()),
+        _ => Err(Error::UnsupportedValueType),
+    }
+}
+
+/// Rejects `manifest` if it declares a host protocol version outside
+/// [`HOST_PROTOCOL_VERSION`] or a capability not in [`HOST_CAPABILITIES`].
+/// Shared by the web and path-loading code paths in [`Service::load_module`]
+/// so both reject an incompatible module before it's ever instantiated.
+fn check_manifest_compat(fqn: &str, manifest: &ModuleManifest) -> Result<()> {
+    let declared = manifest
+        .version()
+        .expect("manifest version is validated while parsing");
+    if !HOST_PROTOCOL_VERSION.matches(&declared) {
+        return Err(Error::IncompatibleManifest {
+            fqn: fqn.to_string(),
+            declared: declared.to_string(),
+        });
+    }
+    for capability in manifest.capabilities() {
+        if !HOST_CAPABILITIES.contains(&capability.as_str()) {
+            return Err(Error::UnsupportedCapability {
+                fqn: fqn.to_string(),
+                capability: capability.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
 fn canonicalize_name<P: AsRef<Path>>(path: P) -> Result<String> {
     Ok(path
         .as_ref()
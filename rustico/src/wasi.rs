@@ -0,0 +1,42 @@
+//! Opt-in WASI support alongside the custom `wotto.*` host functions: a
+//! module compiled from an ordinary Rust/C toolchain that expects
+//! `wasi_snapshot_preview1` imports (clocks, random, args, stdio) instead of
+//! the hand-written `wotto.*`/AssemblyScript ABI can run unmodified.
+//!
+//! The imports are always linked (there's no way to make a shared
+//! [`wasmtime::Linker`] conditionally expose a namespace per instantiation),
+//! so the actual gate is in [`crate::service`]: a module that imports
+//! `wasi_snapshot_preview1` while its [`Capabilities::allow_wasi`] is `false`
+//! is refused before it's ever instantiated.
+
+use wasmtime_wasi::pipe::MemoryOutputPipe;
+use wasmtime_wasi::preview1::WasiP1Ctx;
+use wasmtime_wasi::WasiCtxBuilder;
+
+use crate::capabilities::Capabilities;
+
+/// The namespace every `wasi_snapshot_preview1` import lives under.
+pub(crate) const WASI_PREVIEW1_MODULE: &str = "wasi_snapshot_preview1";
+
+/// A module's WASI context, plus handles to its stdout/stderr pipes so
+/// their contents can be drained into [`crate::service::HasOutput`]'s
+/// capacity-bounded buffer once a call completes.
+pub(crate) struct WasiState {
+    pub(crate) ctx: WasiP1Ctx,
+    pub(crate) stdout: MemoryOutputPipe,
+    pub(crate) stderr: MemoryOutputPipe,
+}
+
+/// Build a module's `WasiState`. stdout/stderr are capped at
+/// `capabilities.output_budget`, the same ceiling `wotto.output` enforces,
+/// so a module can't sidestep the output budget just by writing through
+/// WASI instead.
+pub(crate) fn build_state(capabilities: &Capabilities) -> WasiState {
+    let stdout = MemoryOutputPipe::new(capabilities.output_budget);
+    let stderr = MemoryOutputPipe::new(capabilities.output_budget);
+    let mut builder = WasiCtxBuilder::new();
+    builder.stdout(stdout.clone());
+    builder.stderr(stderr.clone());
+    let ctx = builder.build_p1();
+    WasiState { ctx, stdout, stderr }
+}
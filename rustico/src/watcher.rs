@@ -0,0 +1,122 @@
+//! Background filesystem watcher that hot-reloads path-loaded modules,
+//! modeled on Deno's `file_watcher`: filesystem events are debounced so a
+//! burst of writes to the same file only triggers one recompile, and a
+//! module whose source fails to compile keeps its previously-good version
+//! live in the map instead of being removed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use wasmtime::{Engine, Module};
+
+/// Bursts of filesystem events within this window are coalesced into a
+/// single recompile per path.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watch `roots` for changes and recompile the entry in `modules` whose
+/// recorded path (in `module_paths`) matches the changed file. Aborting the
+/// returned handle stops watching; dropping it only detaches the task, which
+/// keeps watching.
+pub(crate) fn spawn(
+    engine: Engine,
+    modules: Arc<Mutex<HashMap<String, Module>>>,
+    module_paths: Arc<Mutex<HashMap<String, PathBuf>>>,
+    roots: Vec<PathBuf>,
+) -> JoinHandle<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = match RecommendedWatcher::new(
+        move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                let _ = tx.send(event);
+            }
+        },
+        notify::Config::default(),
+    ) {
+        Ok(watcher) => watcher,
+        Err(error) => {
+            error!(%error, "failed to start filesystem watcher");
+            return tokio::spawn(async {});
+        }
+    };
+
+    for root in &roots {
+        if let Err(error) = watcher.watch(root, RecursiveMode::Recursive) {
+            error!(path = %root.display(), %error, "failed to watch path");
+        }
+    }
+
+    tokio::spawn(async move {
+        // kept alive for the lifetime of the task; dropping it stops events
+        let _watcher = watcher;
+        let mut pending = HashMap::new();
+
+        while let Some(event) = rx.recv().await {
+            if !is_relevant(&event.kind) {
+                continue;
+            }
+            pending.extend(event.paths.into_iter().map(|path| (path, ())));
+
+            while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                if is_relevant(&event.kind) {
+                    pending.extend(event.paths.into_iter().map(|path| (path, ())));
+                }
+            }
+
+            for path in pending.drain().map(|(path, ())| path) {
+                reload_path(&engine, &modules, &module_paths, &path).await;
+            }
+        }
+    })
+}
+
+fn is_relevant(kind: &notify::EventKind) -> bool {
+    matches!(kind, notify::EventKind::Modify(_) | notify::EventKind::Create(_))
+}
+
+async fn reload_path(
+    engine: &Engine,
+    modules: &Mutex<HashMap<String, Module>>,
+    module_paths: &Mutex<HashMap<String, PathBuf>>,
+    path: &Path,
+) {
+    let fqn = {
+        let module_paths = module_paths.lock().await;
+        module_paths
+            .iter()
+            .find(|(_, module_path)| module_path.as_path() == path)
+            .map(|(fqn, _)| fqn.clone())
+    };
+    let Some(fqn) = fqn else {
+        return;
+    };
+
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            error!(module = fqn, path = %path.display(), %error, "failed to read changed module");
+            return;
+        }
+    };
+
+    match Module::new(engine, bytes) {
+        Ok(module) => {
+            modules.lock().await.insert(fqn.clone(), module);
+            info!(module = fqn, path = %path.display(), "hot-reloaded module");
+        }
+        Err(error) => {
+            error!(
+                module = fqn,
+                path = %path.display(),
+                %error,
+                "keeping previous module live after failed reload"
+            );
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Per-origin HTTP credentials and challenge-response authentication for
+//! sources that require it (private gists, internal mirrors behind a 401),
+//! generalizing [`super::credentials`]'s GitHub-specific bearer tokens to
+//! arbitrary origins and the standard `Basic`/`Digest` (RFC 7616) schemes.
+//!
+//! Credentials are never inferred from a url (see
+//! [`super::InvalidUrl::CredentialsNotAllowed`]) — only an origin an
+//! operator has explicitly [`trust_credentials`]-ed ever receives them.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use md5::Md5;
+use rand::RngCore;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use url::Origin;
+
+use super::WebError;
+use crate::service::Result;
+
+/// A username/password pair trusted to answer auth challenges from some
+/// [`Origin`].
+#[derive(Clone)]
+struct Credential {
+    username: String,
+    password: String,
+}
+
+lazy_static! {
+    /// Origins an operator has explicitly trusted with credentials. A host
+    /// that isn't here never receives a username/password, no matter what a
+    /// `WWW-Authenticate` challenge asks for.
+    static ref ORIGIN_MAP: Mutex<HashMap<Origin, Credential>> = Mutex::new(HashMap::new());
+    /// Per-nonce request counters for Digest's `nc` parameter, keyed by the
+    /// nonce string a server issued. A nonce we haven't used yet starts at 1.
+    static ref NONCE_COUNTS: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Trust `username`/`password` to answer auth challenges from `origin`.
+pub(crate) fn trust_credentials(
+    origin: Origin,
+    username: impl Into<String>,
+    password: impl Into<String>,
+) {
+    ORIGIN_MAP.lock().unwrap().insert(
+        origin,
+        Credential {
+            username: username.into(),
+            password: password.into(),
+        },
+    );
+}
+
+fn credential_for(origin: &Origin) -> Option<Credential> {
+    ORIGIN_MAP.lock().unwrap().get(origin).cloned()
+}
+
+/// Execute a request built fresh by `build` each attempt. If the first
+/// attempt is challenged with a `401` and `origin` has credentials on file,
+/// retries once with an `Authorization` header answering the scheme the
+/// first `WWW-Authenticate` header names (Digest or Basic; any other scheme
+/// surfaces as [`WebError::Unauthorized`], and later `WWW-Authenticate`
+/// headers are not consulted). A `401` from an origin with no credentials on file surfaces as
+/// [`WebError::NoCredentials`] rather than being retried.
+pub(crate) async fn execute_with_auth(
+    client: &Client,
+    origin: &Origin,
+    build: impl Fn() -> RequestBuilder,
+) -> Result<Response> {
+    let request = build().build().map_err(WebError::ReqwestError)?;
+    let method = request.method().as_str().to_string();
+    let uri = request.url().path().to_string();
+    let response = client
+        .execute(request)
+        .await
+        .map_err(WebError::TemporaryFailure)?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    let credential = credential_for(origin).ok_or(WebError::NoCredentials)?;
+    let challenge = response
+        .headers()
+        .get("www-authenticate")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| authorization_header(value, &credential, &method, &uri))
+        .ok_or(WebError::Unauthorized)?;
+
+    let retry = build()
+        .header("authorization", challenge)
+        .build()
+        .map_err(WebError::ReqwestError)?;
+    client
+        .execute(retry)
+        .await
+        .map_err(WebError::TemporaryFailure)
+        .map_err(Into::into)
+}
+
+/// Builds the `Authorization` header value answering `challenge` (the raw
+/// `WWW-Authenticate` header), or `None` if it names a scheme other than
+/// `Basic`/`Digest`.
+fn authorization_header(
+    challenge: &str,
+    credential: &Credential,
+    method: &str,
+    uri: &str,
+) -> Option<String> {
+    if let Some(params) = challenge.strip_prefix("Digest ") {
+        digest_authorization(params, credential, method, uri)
+    } else if challenge.trim_start().starts_with("Basic") {
+        Some(basic_authorization(credential))
+    } else {
+        None
+    }
+}
+
+fn basic_authorization(credential: &Credential) -> String {
+    use base64::Engine as _;
+    let raw = format!("{}:{}", credential.username, credential.password);
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(raw)
+    )
+}
+
+/// Which hash function a Digest challenge's `algorithm` token names. Only
+/// the two RFC 7616 defines (MD5 for back-compat, SHA-256 for everything
+/// since) are supported; an unrecognized token falls back to MD5, the
+/// scheme's original default.
+enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    fn from_token(token: Option<&str>) -> Self {
+        match token.map(str::to_ascii_uppercase).as_deref() {
+            Some("SHA-256") => Self::Sha256,
+            _ => Self::Md5,
+        }
+    }
+
+    fn hash_hex(&self, input: &str) -> String {
+        let bytes: Vec<u8> = match self {
+            Self::Md5 => Md5::digest(input.as_bytes()).to_vec(),
+            Self::Sha256 => Sha256::digest(input.as_bytes()).to_vec(),
+        };
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            write!(hex, "{byte:02x}").unwrap();
+        }
+        hex
+    }
+}
+
+/// Builds a Digest `Authorization` header per RFC 7616: `HA1 =
+/// H(username:realm:password)`, `HA2 = H(method:digest-uri)`, and `response
+/// = H(HA1:nonce:nc:cnonce:qop:HA2)` when the challenge offers `qop=auth`
+/// (the common case), or the legacy `H(HA1:nonce:HA2)` otherwise.
+fn digest_authorization(
+    params: &str,
+    credential: &Credential,
+    method: &str,
+    uri: &str,
+) -> Option<String> {
+    let challenge = parse_digest_params(params);
+    let realm = challenge.get("realm")?;
+    let nonce = challenge.get("nonce")?;
+    let algorithm = DigestAlgorithm::from_token(challenge.get("algorithm").map(String::as_str));
+    let supports_auth_qop = challenge
+        .get("qop")
+        .is_some_and(|qop| qop.split(',').any(|token| token.trim() == "auth"));
+
+    let ha1 = algorithm.hash_hex(&format!(
+        "{}:{realm}:{}",
+        credential.username, credential.password
+    ));
+    let ha2 = algorithm.hash_hex(&format!("{method}:{uri}"));
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{realm}\", nonce=\"{nonce}\", uri=\"{uri}\"",
+        credential.username
+    );
+
+    let response = if supports_auth_qop {
+        let nc = format!("{:08x}", next_nonce_count(nonce));
+        let cnonce = generate_cnonce();
+        let response = algorithm.hash_hex(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}"));
+        write!(header, ", qop=auth, nc={nc}, cnonce=\"{cnonce}\"").ok()?;
+        response
+    } else {
+        algorithm.hash_hex(&format!("{ha1}:{nonce}:{ha2}"))
+    };
+    write!(header, ", response=\"{response}\"").ok()?;
+
+    if let Some(opaque) = challenge.get("opaque") {
+        write!(header, ", opaque=\"{opaque}\"").ok()?;
+    }
+    if let Some(algorithm) = challenge.get("algorithm") {
+        write!(header, ", algorithm={algorithm}").ok()?;
+    }
+    Some(header)
+}
+
+/// The next `nc` (nonce count) to use with `nonce`, incrementing a
+/// per-nonce counter so a server can detect replayed requests.
+fn next_nonce_count(nonce: &str) -> u32 {
+    let mut counts = NONCE_COUNTS.lock().unwrap();
+    let count = counts.entry(nonce.to_string()).or_insert(0);
+    *count += 1;
+    *count
+}
+
+/// A fresh random client nonce, hex-encoded.
+fn generate_cnonce() -> String {
+    let mut bytes = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    hex
+}
+
+/// Parses a Digest challenge's comma-separated parameter list into a
+/// lowercase-keyed map, stripping quotes from quoted values. Commas inside
+/// a quoted value (e.g. a `qop` list like `"auth,auth-int"`) don't split it.
+fn parse_digest_params(params: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for part in split_unquoted_commas(params) {
+        if let Some((key, value)) = part.split_once('=') {
+            map.insert(
+                key.trim().to_ascii_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            );
+        }
+    }
+    map
+}
+
+fn split_unquoted_commas(params: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                parts.push(params[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(params[start..].trim());
+    parts
+}
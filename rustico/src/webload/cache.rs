@@ -0,0 +1,137 @@
+//! Caching for GitHub Gist and repository fetches.
+//!
+//! A raw file fetch is keyed by its blob sha (gists) or resolved commit sha
+//! (repos), which is content-addressed: once we have the bytes for a given
+//! key they can never change, so repeat requests are served from memory
+//! without a network round-trip. Metadata responses (a gist's file listing,
+//! a repo ref's commit sha) are not content-addressed, so they're cached per
+//! logical key and revalidated with the previous response's `ETag`, reusing
+//! the cached copy on a `304 Not Modified`.
+
+use lazy_static::lazy_static;
+use url::Url;
+
+use crate::registry::{FetchOutcome, Registry};
+use crate::service::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct BlobKey {
+    pub(super) gist_id: String,
+    pub(super) blob: String,
+    pub(super) file_path: String,
+}
+
+/// A fetched blob's bytes, plus the url it was actually served from once any
+/// redirects were followed. Cached alongside the content itself (rather than
+/// re-derived on every cache hit) since a given content-addressed key always
+/// redirects the same way.
+#[derive(Debug, Clone)]
+pub(super) struct CachedBlob {
+    pub(super) content: Vec<u8>,
+    pub(super) resolved_url: Url,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct MetadataKey {
+    pub(super) gist_id: String,
+    pub(super) commit: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct CachedMetadata {
+    pub(super) json: serde_json::Value,
+    pub(super) etag: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct RepoBlobKey {
+    pub(super) host: &'static str,
+    pub(super) user: String,
+    pub(super) repo: String,
+    pub(super) sha: String,
+    pub(super) file_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct RepoRefKey {
+    pub(super) host: &'static str,
+    pub(super) user: String,
+    pub(super) repo: String,
+    pub(super) git_ref: String,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct CachedRef {
+    pub(super) sha: String,
+    pub(super) etag: Option<String>,
+}
+
+/// A blob read out of a [`super::git::GitLoader`] checkout, keyed by the
+/// repository's short hash and the pinned commit, which (unlike a
+/// branch/tag name) never changes meaning.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct GitBlobKey {
+    pub(super) short_hash: String,
+    pub(super) rev: String,
+    pub(super) file_path: String,
+}
+
+lazy_static! {
+    static ref BLOBS: Registry<BlobKey, CachedBlob> = Registry::default();
+    static ref METADATA: Registry<MetadataKey, CachedMetadata> = Registry::default();
+    static ref REPO_BLOBS: Registry<RepoBlobKey, CachedBlob> = Registry::default();
+    static ref REPO_REFS: Registry<RepoRefKey, CachedRef> = Registry::default();
+    static ref GIT_BLOBS: Registry<GitBlobKey, Vec<u8>> = Registry::default();
+}
+
+/// Fetch the content addressed by `key`, skipping the network entirely if
+/// it's already cached.
+pub(super) async fn cached_blob<F, Fut>(key: BlobKey, fetch: F) -> Result<CachedBlob>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<CachedBlob>>,
+{
+    BLOBS.get_or_try_insert_with(key, fetch).await
+}
+
+/// Fetch `key`'s metadata, letting `fetch` see the previously cached copy
+/// (if any) so it can issue a conditional request and report
+/// [`FetchOutcome::Reuse`] on a `304`.
+pub(super) async fn cached_metadata<F, Fut>(key: MetadataKey, fetch: F) -> Result<CachedMetadata>
+where
+    F: FnOnce(Option<CachedMetadata>) -> Fut,
+    Fut: std::future::Future<Output = Result<FetchOutcome<CachedMetadata>>>,
+{
+    METADATA.get_or_try_update_with(key, fetch).await
+}
+
+/// Fetch the content addressed by `key`, skipping the network entirely if
+/// it's already cached.
+pub(super) async fn cached_repo_blob<F, Fut>(key: RepoBlobKey, fetch: F) -> Result<CachedBlob>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<CachedBlob>>,
+{
+    REPO_BLOBS.get_or_try_insert_with(key, fetch).await
+}
+
+/// Resolve `key` (a branch/tag/sha reference) to a commit sha, letting
+/// `fetch` see the previously cached copy (if any) so it can issue a
+/// conditional request and report [`FetchOutcome::Reuse`] on a `304`.
+pub(super) async fn cached_repo_ref<F, Fut>(key: RepoRefKey, fetch: F) -> Result<CachedRef>
+where
+    F: FnOnce(Option<CachedRef>) -> Fut,
+    Fut: std::future::Future<Output = Result<FetchOutcome<CachedRef>>>,
+{
+    REPO_REFS.get_or_try_update_with(key, fetch).await
+}
+
+/// Fetch the content addressed by `key`, skipping the local checkout
+/// entirely if it's already cached.
+pub(super) async fn cached_git_blob<F, Fut>(key: GitBlobKey, fetch: F) -> Result<Vec<u8>>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<u8>>>,
+{
+    GIT_BLOBS.get_or_try_insert_with(key, fetch).await
+}
@@ -0,0 +1,243 @@
+//! Credential resolution, pluggable per host, and rate-limit-aware request
+//! execution shared by every HTTP-backed loader.
+//!
+//! Resolution for a given host label (`"github"`, `"gitlab"`, or a
+//! self-hosted instance's own label) tries each registered
+//! [`CredentialProvider`] in turn: an explicit `<HOST>_TOKEN` environment
+//! variable first, then a `wotto-credentials.toml` config file listing one
+//! `[[host]]` table per host (modeled on crev's per-host trust config), then
+//! anonymous access. This replaces the old github-only `github.token` file
+//! and `basic_auth(user, password)`, which GitHub has deprecated in favor of
+//! bearer tokens.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use reqwest::{Client, Response, StatusCode};
+use serde::Deserialize;
+
+use super::WebError;
+use crate::service::Result;
+
+const CREDENTIALS_CONFIG_PATH: &str = "wotto-credentials.toml";
+
+/// How a [`Credentials`] token should be attached to an outgoing request,
+/// since GitHub and GitLab disagree on the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum TokenScheme {
+    /// `Authorization: Bearer <token>` — GitHub's current token format.
+    Bearer,
+    /// `PRIVATE-TOKEN: <token>` — GitLab's own header, not `Authorization`.
+    PrivateToken,
+}
+
+impl Default for TokenScheme {
+    fn default() -> Self {
+        Self::Bearer
+    }
+}
+
+/// A resolved token for one host, along with how it should be applied.
+#[derive(Clone)]
+pub(super) struct Credentials {
+    token: String,
+    scheme: TokenScheme,
+}
+
+impl Credentials {
+    pub(super) fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.scheme {
+            TokenScheme::Bearer => request.bearer_auth(&self.token),
+            TokenScheme::PrivateToken => request.header("PRIVATE-TOKEN", &self.token),
+        }
+    }
+
+    /// Resolve credentials for `host` (e.g. `"github"`, `"gitlab"`) by
+    /// trying each registered [`CredentialProvider`] in turn. Returns `None`
+    /// when none of them have anything configured for `host`, so callers can
+    /// still make unauthenticated requests against public gists and repos.
+    pub(super) fn resolve(host: &str) -> Option<Self> {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .providers
+            .iter()
+            .find_map(|provider| provider.resolve(host))
+    }
+}
+
+/// A source of per-host credentials. Providers are tried in registration
+/// order; the first to return `Some` wins. Lets a self-hosted GitLab (or any
+/// other host [`super::repo`] learns to speak to) register its own token
+/// format without this module needing to know about it up front.
+pub(super) trait CredentialProvider: Send + Sync {
+    fn resolve(&self, host: &str) -> Option<Credentials>;
+}
+
+/// `<HOST>_TOKEN` environment variables, e.g. `GITHUB_TOKEN`, `GITLAB_TOKEN`
+/// — always sent as a bearer token, which both GitHub and GitLab accept even
+/// though GitLab's own tooling prefers `PRIVATE-TOKEN`.
+struct EnvProvider;
+
+impl CredentialProvider for EnvProvider {
+    fn resolve(&self, host: &str) -> Option<Credentials> {
+        let var = format!("{}_TOKEN", host.to_ascii_uppercase());
+        let token = std::env::var(var).ok().filter(|t| !t.is_empty())?;
+        Some(Credentials {
+            token,
+            scheme: TokenScheme::Bearer,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HostEntry {
+    name: String,
+    token: String,
+    #[serde(default)]
+    scheme: TokenScheme,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct CredentialsManifest {
+    #[serde(default)]
+    host: Vec<HostEntry>,
+}
+
+/// `wotto-credentials.toml`'s `[[host]]` entries, keyed by host name, read
+/// once at first resolve. Missing or unparseable config is treated the same
+/// as an empty one, so a fresh checkout with no file at all just falls
+/// through to anonymous access.
+struct ConfigFileProvider {
+    hosts: HashMap<String, Credentials>,
+}
+
+impl ConfigFileProvider {
+    fn load() -> Self {
+        let hosts = std::fs::read_to_string(CREDENTIALS_CONFIG_PATH)
+            .ok()
+            .and_then(|contents| toml::from_str::<CredentialsManifest>(&contents).ok())
+            .map(|manifest| {
+                manifest
+                    .host
+                    .into_iter()
+                    .map(|entry| {
+                        (
+                            entry.name,
+                            Credentials {
+                                token: entry.token,
+                                scheme: entry.scheme,
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { hosts }
+    }
+}
+
+impl CredentialProvider for ConfigFileProvider {
+    fn resolve(&self, host: &str) -> Option<Credentials> {
+        self.hosts.get(host).cloned()
+    }
+}
+
+struct ProviderRegistry {
+    providers: Vec<Arc<dyn CredentialProvider>>,
+}
+
+impl ProviderRegistry {
+    fn with_defaults() -> Self {
+        Self {
+            providers: vec![Arc::new(EnvProvider), Arc::new(ConfigFileProvider::load())],
+        }
+    }
+
+    fn register(&mut self, provider: Arc<dyn CredentialProvider>) {
+        self.providers.push(provider);
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<ProviderRegistry> = Mutex::new(ProviderRegistry::with_defaults());
+}
+
+/// Adds `provider` ahead of anonymous access but after every
+/// already-registered provider, for a self-hosted instance to supply its own
+/// token format/base API url without forking this module. See
+/// [`super::repo::Host`] for where a new host label would also need to be
+/// taught how to build urls.
+#[allow(dead_code)]
+pub(super) fn register_credential_provider(provider: Arc<dyn CredentialProvider>) {
+    REGISTRY.lock().unwrap().register(provider);
+}
+
+const MAX_RETRIES: u32 = 3;
+
+fn header_u64(response: &Response, name: &str) -> Option<u64> {
+    response.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Delay to wait before retrying, derived from `Retry-After` or GitHub's
+/// `X-RateLimit-Reset` (a unix timestamp), whichever is present.
+fn retry_delay(response: &Response) -> Option<Duration> {
+    if let Some(seconds) = header_u64(response, "retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+    let reset_at = header_u64(response, "x-ratelimit-reset")?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(Duration::from_secs(reset_at.saturating_sub(now)))
+}
+
+/// Execute a request built fresh by `build` on each attempt, retrying with
+/// bounded backoff when the host signals rate limiting, and mapping `401`/
+/// `403`/`429` to distinct, recognizable errors instead of a generic failure.
+pub(super) async fn execute_with_retry(
+    client: &Client,
+    build: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<Response> {
+    for attempt in 0..=MAX_RETRIES {
+        let request = build().build().map_err(WebError::ReqwestError)?;
+        let response = client
+            .execute(request)
+            .await
+            .map_err(WebError::TemporaryFailure)?;
+
+        match response.status() {
+            StatusCode::UNAUTHORIZED => return Err(WebError::Unauthorized.into()),
+            StatusCode::NOT_FOUND => return Err(WebError::NotFound.into()),
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS => {
+                if header_u64(&response, "x-ratelimit-remaining") == Some(0)
+                    || response.status() == StatusCode::TOO_MANY_REQUESTS
+                {
+                    if attempt < MAX_RETRIES {
+                        if let Some(delay) = retry_delay(&response) {
+                            tokio::time::sleep(delay).await;
+                            continue;
+                        }
+                    }
+                    let reset_at = header_u64(&response, "x-ratelimit-reset");
+                    return Err(WebError::RateLimited { reset_at }.into());
+                }
+                return response
+                    .error_for_status()
+                    .map_err(WebError::TemporaryFailure)
+                    .map_err(Into::into);
+            }
+            _ => {
+                return response
+                    .error_for_status()
+                    .map_err(WebError::TemporaryFailure)
+                    .map_err(Into::into)
+            }
+        }
+    }
+    unreachable!("loop always returns within MAX_RETRIES + 1 iterations")
+}
@@ -1,6 +1,14 @@
-use super::{Domain, InvalidUrl, ResolvedModule, ResolverResult, WebError};
+use super::cache::{self, BlobKey, CachedBlob, CachedMetadata, MetadataKey};
+use super::credentials::{execute_with_retry, Credentials};
+use super::gistlock::GistLockEntry;
+use super::integrity::Integrity;
+use super::{
+    client, download_with_limit, gistgit, gistlock, manifest, BoxFuture, Domain, InvalidUrl,
+    ResolvedModule, ResolverResult, WebError, WebLoader, MAX_MODULE_SIZE,
+};
+use crate::registry::FetchOutcome;
 use crate::service::Result;
-use url::Url;
+use url::{Origin, Url};
 
 fn is_hex_string(s: &str) -> bool {
     !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
@@ -227,12 +235,10 @@ fn extract_gist_from_json(json: serde_json::Value, gist: Gist) -> Option<GistRes
     //    already made for the correct revision, so we can assume that the file
     //    we see here is the file we need
     // 3) a raw url was given, which contains a blob sha, but not a commit sha;
-    //    we have the option to validate that the url matches the info on gh,
-    //    but that would require us to either make multiple api calls until we
-    //    find a file matching the blob sha, or use git directly to fetch the
-    //    right object (afaik the gists api doesn't have a way to fetch by
-    //    blob). since neither is implemented (maybe todo?) what we do now is
-    //    just to trust the info given in the raw url.
+    //    the Gists API has no way to fetch by blob, so unless the latest
+    //    revision's raw url carries that same blob, we mark it unverified and
+    //    let `load_content` try the gist's own git repository first via
+    //    `gistgit::fetch_blob`.
 
     let raw_url: Url = file.get("raw_url")?.as_str()?.parse().ok()?;
 
@@ -250,14 +256,18 @@ fn extract_gist_from_json(json: serde_json::Value, gist: Gist) -> Option<GistRes
                 name,
                 blob,
                 content.map(|s| s.bytes().collect()),
+                true,
             ))
         } else {
-            // we disregard the json entirely
+            // we disregard the json entirely; the blob sha is unverified, so
+            // `load_content` will first try to check it out of the gist's own
+            // git repository, and only falls back to `build_raw_url()`'s
+            // guess if that fails
             let file_path = gist
                 .file_path()
                 .expect("raw url Gists should always be created with a file_path")
                 .to_string();
-            Some(GistResolvedModule::new(gist, file_path, blob, None))
+            Some(GistResolvedModule::new(gist, file_path, blob, None, false))
         }
     } else {
         // either case 1 or 2, which are handled the same way
@@ -268,27 +278,11 @@ fn extract_gist_from_json(json: serde_json::Value, gist: Gist) -> Option<GistRes
             name,
             blob,
             content.map(|s| s.bytes().collect()),
+            true,
         ))
     }
 }
 
-fn github_basic_auth() -> Result<(String, String)> {
-    let text = std::fs::read_to_string("github.token").map_err(|_| WebError::NoCredentials)?;
-    let lines: Vec<_> = text.split_ascii_whitespace().take(2).collect();
-    match lines[..] {
-        [username, password] => Ok((username.to_string(), password.to_string())),
-        _ => Err(WebError::NoCredentials.into()),
-    }
-}
-
-fn client() -> Result<reqwest::Client> {
-    Ok(reqwest::ClientBuilder::new()
-        .user_agent("https://github.com/sorcio/rusto")
-        .https_only(true)
-        .build()
-        .map_err(WebError::ReqwestError)?)
-}
-
 pub(super) async fn resolve_gist(url: &Url) -> Result<impl ResolverResult> {
     debug_assert_eq!(url.scheme(), "https");
     debug_assert!(matches!(
@@ -298,59 +292,130 @@ pub(super) async fn resolve_gist(url: &Url) -> Result<impl ResolverResult> {
         ))
     ));
 
+    // A url pinned by an earlier resolve always reuses that exact identity,
+    // so a gist that's edited or force-pushed after being loaded once can't
+    // silently swap in different content under the same url.
+    if let Some(locked) = gistlock::get(url.as_str()).await {
+        return Ok(GistResolvedModule::from_locked(locked));
+    }
+
     let gist = Gist::new(url)?;
     let api_url = gist.build_api_url();
     let client = client()?;
-    let (username, password) = github_basic_auth()?;
-    let request = client
-        .request(reqwest::Method::GET, api_url)
-        .header("Accept", "application/vnd.github+json")
-        .header("X-GitHub-Api-Version", "2022-11-28")
-        .basic_auth(&username, Some(&password))
-        .build()
-        .map_err(WebError::ReqwestError)?;
-    let response = client
-        .execute(request)
-        .await
-        .map_err(WebError::TemporaryFailure)?
-        .error_for_status()
-        .map_err(WebError::TemporaryFailure)?;
+    let credentials = Credentials::resolve("github");
+    let key = MetadataKey {
+        gist_id: gist.gist_id().to_string(),
+        commit: gist.commit().map(str::to_string),
+    };
+    let cached = cache::cached_metadata(key, |previous| async move {
+        let etag = previous.as_ref().and_then(|m| m.etag.as_deref());
+        let response = execute_with_retry(&client, || {
+            let mut request = client
+                .request(reqwest::Method::GET, &api_url)
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", "2022-11-28");
+            if let Some(etag) = etag {
+                request = request.header("If-None-Match", etag);
+            }
+            match &credentials {
+                Some(credentials) => credentials.apply(request),
+                None => request,
+            }
+        })
+        .await?;
 
-    let json = response
-        .json::<serde_json::Value>()
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(FetchOutcome::Reuse);
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let json = response
+            .json::<serde_json::Value>()
+            .await
+            .map_err(WebError::ReqwestError)?;
+        Ok(FetchOutcome::Replace(CachedMetadata { json, etag }))
+    })
+    .await?;
+
+    let resolved = extract_gist_from_json(cached.json, gist).ok_or(WebError::NotWasm)?;
+    gistlock::record(url.as_str().to_string(), resolved.to_lock_entry())
         .await
-        .map_err(WebError::ReqwestError)?;
-
-    extract_gist_from_json(json, gist).ok_or(WebError::NotWasm.into())
+        .map_err(WebError::LockfileIo)?;
+    Ok(resolved)
 }
 
 pub(crate) async fn load_content(module: &mut ResolvedModule) -> Result<()> {
     if module.content().is_some() {
         return Ok(());
     }
+    let url = module.url().as_str().to_string();
     let resolver_result = module.downcast::<GistResolvedModule>();
+    let key = BlobKey {
+        gist_id: resolver_result.gist_id.clone(),
+        blob: resolver_result.blob.clone(),
+        file_path: resolver_result.file_path.clone(),
+    };
     let fetch_url = resolver_result.build_raw_url();
+    let verified = resolver_result.verified;
+    let gist_id = resolver_result.gist_id.clone();
+    let blob = resolver_result.blob.clone();
 
-    let client = reqwest::ClientBuilder::new()
-        .user_agent("https://github.com/sorcio/rusto")
-        .https_only(true)
-        .build()
-        .map_err(WebError::ReqwestError)?;
-    let (username, password) = github_basic_auth()?;
-    let request = client
-        .request(reqwest::Method::GET, fetch_url)
-        .header("Accept", "application/vnd.github.raw")
-        .basic_auth(&username, Some(&password))
-        .build()
-        .map_err(WebError::ReqwestError)?;
-    let response = client
-        .execute(request)
-        .await
-        .map_err(WebError::TemporaryFailure)?
-        .error_for_status()
-        .map_err(WebError::TemporaryFailure)?;
-    let content = response.bytes().await.map_err(WebError::TemporaryFailure)?;
-    resolver_result.set_content(content);
+    let client = client()?;
+    let credentials = Credentials::resolve("github");
+    let cached = cache::cached_blob(key, || async move {
+        // An unverified blob sha can't be trusted to actually live at
+        // `fetch_url`'s guessed revision; try reading it directly out of the
+        // gist's own git repository first, and fall back to guessing over
+        // HTTP only if that fails or doesn't yield a wasm module.
+        if !verified {
+            if let Ok(content) = gistgit::fetch_blob(&gist_id, &blob).await {
+                if manifest::looks_like_wasm_module(&content) {
+                    return Ok(CachedBlob {
+                        content,
+                        resolved_url: gistgit::resolved_url(&gist_id),
+                    });
+                }
+            }
+        }
+
+        let response = execute_with_retry(&client, || {
+            let request = client
+                .request(reqwest::Method::GET, &fetch_url)
+                .header("Accept", "application/vnd.github.raw");
+            match &credentials {
+                Some(credentials) => credentials.apply(request),
+                None => request,
+            }
+        })
+        .await?;
+        let resolved_url = response.url().clone();
+        let content = download_with_limit(response, MAX_MODULE_SIZE).await?;
+        if !manifest::looks_like_wasm_module(&content) {
+            return Err(WebError::NotWasm.into());
+        }
+        Ok(CachedBlob { content, resolved_url })
+    })
+    .await?;
+
+    if let Some(expected) = &resolver_result.integrity {
+        if !expected.matches(&cached.content) {
+            return Err(WebError::IntegrityMismatch.into());
+        }
+    } else {
+        resolver_result.integrity = Some(Integrity::compute_sha256(&cached.content));
+        // resolve_gist's own gistlock::record ran before content existed, so
+        // the entry it wrote has no integrity yet; fill it in now that one's
+        // been computed for the first time.
+        gistlock::record(url, resolver_result.to_lock_entry())
+            .await
+            .map_err(WebError::LockfileIo)?;
+    }
+
+    resolver_result.set_content(cached.content);
+    module.set_resolved_url(cached.resolved_url);
     Ok(())
 }
 
@@ -359,20 +424,55 @@ struct GistResolvedModule {
     gist_id: String,
     file_path: String,
     blob: String,
+    /// Whether `blob` is known (from the Gists API response) to match the
+    /// requested revision, as opposed to a raw url's blob sha taken on
+    /// trust. See `extract_gist_from_json`'s case (3).
+    verified: bool,
+    /// The expected (or, once content is fetched for the first time,
+    /// recorded) digest of this module's content. `None` until either a
+    /// pinned [`gistlock`] entry supplies one or `load_content` computes one.
+    integrity: Option<Integrity>,
     content: Option<Vec<u8>>,
 }
 
 impl GistResolvedModule {
-    fn new(gist: Gist, file_path: String, blob: String, content: Option<Vec<u8>>) -> Self {
+    fn new(gist: Gist, file_path: String, blob: String, content: Option<Vec<u8>>, verified: bool) -> Self {
         Self {
             user: gist.user().to_string(),
             gist_id: gist.gist_id().to_string(),
             file_path,
             blob,
+            verified,
+            integrity: None,
             content,
         }
     }
 
+    /// Rebuilds the resolved identity pinned by an earlier resolve, trusting
+    /// it exactly (no fresh API call) other than the integrity check
+    /// `load_content` still runs once its content is fetched.
+    fn from_locked(locked: GistLockEntry) -> Self {
+        Self {
+            user: locked.user,
+            gist_id: locked.gist_id,
+            file_path: locked.file_path,
+            blob: locked.blob,
+            verified: true,
+            integrity: locked.integrity.and_then(|s| s.parse().ok()),
+            content: None,
+        }
+    }
+
+    fn to_lock_entry(&self) -> GistLockEntry {
+        GistLockEntry {
+            user: self.user.clone(),
+            gist_id: self.gist_id.clone(),
+            blob: self.blob.clone(),
+            file_path: self.file_path.clone(),
+            integrity: self.integrity.as_ref().map(Integrity::to_string),
+        }
+    }
+
     fn build_raw_url(&self) -> String {
         let Self {
             user,
@@ -403,6 +503,10 @@ impl ResolverResult for GistResolvedModule {
         &self.file_path
     }
 
+    fn cache_identity(&self) -> String {
+        format!("gist:{}/{}/{}", self.gist_id, self.blob, self.file_path)
+    }
+
     fn content(&self) -> Option<&[u8]> {
         self.content.as_deref()
     }
@@ -411,7 +515,62 @@ impl ResolverResult for GistResolvedModule {
         self.content.take()
     }
 
+    fn set_content(&mut self, content: Vec<u8>) {
+        assert!(self.content.is_none(), "set_content() requires that content is None");
+        self.content = Some(content);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
         self
     }
 }
+
+lazy_static! {
+    static ref GIST_ORIGIN: Origin = "https://gist.github.com/".parse::<Url>().unwrap().origin();
+    static ref GIST_RAW_ORIGIN: Origin = "https://gist.githubusercontent.com/"
+        .parse::<Url>()
+        .unwrap()
+        .origin();
+}
+
+/// Handles both `gist.github.com` (API-backed) and
+/// `gist.githubusercontent.com` (raw) origins.
+pub(super) struct GistLoader;
+
+impl WebLoader for GistLoader {
+    fn name(&self) -> &'static str {
+        "gist"
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        *origin == *GIST_ORIGIN || *origin == *GIST_RAW_ORIGIN
+    }
+
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>> {
+        Box::pin(async move {
+            let resolved = resolve_gist(url).await?;
+            Ok(Box::new(resolved) as Box<dyn ResolverResult + Send + Sync>)
+        })
+    }
+
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>> {
+        Box::pin(load_content(module))
+    }
+
+    fn fetch_signature<'a>(
+        &'a self,
+        module: &'a ResolvedModule,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let resolved = module.downcast_ref::<GistResolvedModule>();
+            super::fetch_detached_signature(&resolved.build_raw_url()).await
+        })
+    }
+}
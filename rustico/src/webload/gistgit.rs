@@ -0,0 +1,80 @@
+//! Fetches a gist's raw content by blob sha directly out of its own git
+//! repository, rather than trusting the blob sha embedded in a user-supplied
+//! raw url (see [`super::gist::extract_gist_from_json`]'s case 3): every
+//! gist is itself a small git repository at `https://gist.github.com/<id>.git`,
+//! so once it's fetched into a local bare clone the blob can be read
+//! straight out of it, the same way [`super::git::GitLoader`] reads a path
+//! out of its own checkouts.
+
+use std::path::{Path, PathBuf};
+
+use git2::{AutotagOption, FetchOptions, Oid, Repository};
+use url::Url;
+
+use crate::service::{Error, Result};
+
+/// Where local clones of gist repositories live, one subdirectory per gist
+/// id.
+const GIST_CHECKOUTS_DIR: &str = "wotto-gist-checkouts";
+
+fn clone_url(gist_id: &str) -> String {
+    format!("https://gist.github.com/{gist_id}.git")
+}
+
+fn checkout_dir(gist_id: &str) -> PathBuf {
+    Path::new(GIST_CHECKOUTS_DIR).join(gist_id)
+}
+
+/// Opens the local bare clone for `gist_id` under its checkout dir, creating
+/// it first if this is the first time this gist is loaded, then fetches all
+/// branches so a blob sha from any revision can be found.
+fn open_and_fetch(dir: &Path, clone_url: &str) -> Result<Repository> {
+    std::fs::create_dir_all(dir).map_err(Error::GitCheckoutIo)?;
+    let repo = if dir.join("HEAD").exists() {
+        Repository::open_bare(dir).map_err(Error::Git)?
+    } else {
+        Repository::init_bare(dir).map_err(Error::Git)?
+    };
+
+    let mut remote = match repo.find_remote("origin") {
+        Ok(remote) => remote,
+        Err(_) => repo.remote("origin", clone_url).map_err(Error::Git)?,
+    };
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.download_tags(AutotagOption::All);
+    remote
+        .fetch(
+            &["+refs/heads/*:refs/remotes/origin/*"],
+            Some(&mut fetch_options),
+            None,
+        )
+        .map_err(Error::Git)?;
+
+    Ok(repo)
+}
+
+/// Checks out (or reuses) `gist_id`'s own git repository and reads
+/// `blob_sha` directly out of it, for the one case [`super::gist`] can't
+/// otherwise verify: a raw url's blob sha with no matching commit in the
+/// Gists API response.
+pub(super) async fn fetch_blob(gist_id: &str, blob_sha: &str) -> Result<Vec<u8>> {
+    let dir = checkout_dir(gist_id);
+    let url = clone_url(gist_id);
+    let blob_sha = blob_sha.to_string();
+    tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        let repo = open_and_fetch(&dir, &url)?;
+        let oid = Oid::from_str(&blob_sha).map_err(Error::Git)?;
+        let blob = repo.find_blob(oid).map_err(Error::Git)?;
+        Ok(blob.content().to_vec())
+    })
+    .await
+    .map_err(|_| Error::Wasm(anyhow::anyhow!("gist checkout task panicked")))?
+}
+
+/// The url a blob fetched via [`fetch_blob`] was actually served from, for
+/// [`super::ResolvedModule::set_resolved_url`].
+pub(super) fn resolved_url(gist_id: &str) -> Url {
+    clone_url(gist_id)
+        .parse()
+        .expect("clone url is always valid")
+}
@@ -0,0 +1,76 @@
+//! Pins a gist url to the `(user, gist_id, blob, file_path, integrity)` it
+//! last resolved to, so a url that's already been loaded once never quietly
+//! starts resolving to different content: a gist owner can still edit or
+//! force-push their gist, but [`super::gist::resolve_gist`] will keep
+//! serving the pinned blob (and verify its integrity digest) until the lock
+//! entry is cleared, rather than silently picking up whatever revision the
+//! Gists API now calls latest.
+//!
+//! This is deliberately separate from [`crate::lockfile::Lockfile`], which
+//! pins a module's *fqn* to a content hash once it's actually loaded into
+//! the engine: this one pins a *url* to the resolver identity that produced
+//! it, one layer earlier, so even the `guess_gist_file_name` heuristic used
+//! to pick a default file only ever runs once per url.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+const DEFAULT_GIST_LOCK_PATH: &str = "wotto-gist-lock.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct GistLockEntry {
+    pub(super) user: String,
+    pub(super) gist_id: String,
+    pub(super) blob: String,
+    pub(super) file_path: String,
+    /// The SRI-style integrity string recorded the first time this entry's
+    /// content was fetched, if any.
+    pub(super) integrity: Option<String>,
+}
+
+struct GistLock {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, GistLockEntry>>,
+}
+
+impl GistLock {
+    fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+}
+
+impl Default for GistLock {
+    fn default() -> Self {
+        Self::load(DEFAULT_GIST_LOCK_PATH)
+    }
+}
+
+lazy_static! {
+    static ref GIST_LOCK: GistLock = GistLock::default();
+}
+
+/// The pinned entry for `url`, if one was already recorded.
+pub(super) async fn get(url: &str) -> Option<GistLockEntry> {
+    GIST_LOCK.entries.lock().await.get(url).cloned()
+}
+
+/// Record (or overwrite) `url`'s pinned entry and persist the lock to disk.
+pub(super) async fn record(url: String, entry: GistLockEntry) -> std::io::Result<()> {
+    let mut entries = GIST_LOCK.entries.lock().await;
+    entries.insert(url, entry);
+    let json = serde_json::to_string_pretty(&*entries)
+        .expect("gist lock entries should always serialize");
+    std::fs::write(&GIST_LOCK.path, json)
+}
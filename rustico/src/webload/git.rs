@@ -0,0 +1,374 @@
+//! Loads modules from arbitrary Git remotes, as opposed to
+//! [`super::gist::GistLoader`] and [`super::repo::RepoLoader`], which only
+//! understand `github.com`'s content API. Urls look like
+//! `git+https://git.example.org/user/project[.git][?rev=<ref-or-sha>][#<path>]`,
+//! modeled on how Cargo's git source identifies a dependency: the revision
+//! selector and in-repo file path are kept separate from the repository's
+//! identity, so a checkout is shared across every url that only differs in
+//! which ref or file it asks for.
+
+use std::path::{Path, PathBuf};
+
+use git2::{AutotagOption, FetchOptions, Oid, Repository};
+use url::{Origin, Url};
+
+use super::cache::{self, GitBlobKey};
+use super::{
+    is_full_sha, manifest, BoxFuture, Domain, InvalidUrl, ResolvedModule, ResolverResult, WebError,
+    WebLoader,
+};
+use crate::lockfile::digest;
+use crate::service::{Error, Result};
+
+/// Where local clones of `git+https://`/`git+http://` repositories live, one
+/// subdirectory per [`GitUrl::short_hash`].
+const GIT_CHECKOUTS_DIR: &str = "wotto-git-checkouts";
+
+/// A parsed `git+https://<host>/<user>/<repo>[.git][?rev=<ref-or-sha>][#<path>]`
+/// url.
+struct GitUrl {
+    host: String,
+    user: String,
+    repo: String,
+    rev: Option<String>,
+    file_path: Option<String>,
+}
+
+impl GitUrl {
+    fn parse(url: &Url) -> Result<Self> {
+        let host = url.host_str().ok_or(InvalidUrl::InvalidPath)?.to_string();
+        let trimmed = url.path().trim_matches('/');
+        let mut parts = trimmed.splitn(2, '/');
+        let user = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?
+            .to_string();
+        let repo = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        let repo = repo.strip_suffix(".git").unwrap_or(repo).to_string();
+        let rev = url
+            .query_pairs()
+            .find(|(key, _)| key == "rev")
+            .map(|(_, value)| value.into_owned());
+        let file_path = url.fragment().filter(|f| !f.is_empty()).map(str::to_string);
+        Ok(Self {
+            host,
+            user,
+            repo,
+            rev,
+            file_path,
+        })
+    }
+
+    /// The transport url git itself should clone/fetch from: this url's
+    /// host, user and repo with the revision/file selectors stripped off,
+    /// always over `https://`, even for a `git+http://` url.
+    fn clone_url(&self) -> String {
+        format!("https://{}/{}/{}", self.host, self.user, self.repo)
+    }
+
+    /// The canonical form of the repository's identity: lowercase host, no
+    /// trailing `.git`, no revision selector or file-path fragment. Modeled
+    /// on Cargo's `CanonicalUrl`, so `?rev=main#a.wasm` and `?rev=v1#b.wasm`
+    /// against the same repo share one checkout.
+    fn canonical_url(&self) -> String {
+        format!(
+            "https://{}/{}/{}",
+            self.host.to_ascii_lowercase(),
+            self.user,
+            self.repo
+        )
+    }
+
+    /// A short stable hex identifier derived from [`Self::canonical_url`],
+    /// used to name this repository's checkout directory.
+    fn short_hash(&self) -> String {
+        digest(self.canonical_url().as_bytes())[..16].to_string()
+    }
+}
+
+fn checkout_dir(short_hash: &str) -> PathBuf {
+    Path::new(GIT_CHECKOUTS_DIR).join(short_hash)
+}
+
+/// Opens the local bare clone for `clone_url` under `dir`, creating it first
+/// if this is the first time this repository is loaded, then fetches all
+/// branches and tags so `rev` can be resolved against up-to-date refs.
+fn open_and_fetch(dir: &Path, clone_url: &str) -> Result<Repository> {
+    std::fs::create_dir_all(dir).map_err(Error::GitCheckoutIo)?;
+    let repo = if dir.join("HEAD").exists() {
+        Repository::open_bare(dir).map_err(Error::Git)?
+    } else {
+        Repository::init_bare(dir).map_err(Error::Git)?
+    };
+
+    let mut remote = match repo.find_remote("origin") {
+        Ok(remote) => remote,
+        Err(_) => repo.remote("origin", clone_url).map_err(Error::Git)?,
+    };
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.download_tags(AutotagOption::All);
+    remote
+        .fetch(
+            &[
+                "+refs/heads/*:refs/remotes/origin/*",
+                "+refs/tags/*:refs/tags/*",
+            ],
+            Some(&mut fetch_options),
+            None,
+        )
+        .map_err(Error::Git)?;
+
+    // a bare init + fetch, unlike a clone, never creates `origin/HEAD`;
+    // point it at the remote's default branch for `resolve_rev`
+    if let Ok(default_branch) = remote.default_branch() {
+        if let Some(branch) = default_branch.as_str().and_then(|b| b.strip_prefix("refs/heads/")) {
+            let target = format!("refs/remotes/origin/{branch}");
+            let _ = repo.reference_symbolic("refs/remotes/origin/HEAD", &target, true, "set origin/HEAD");
+        }
+    }
+
+    Ok(repo)
+}
+
+/// Resolves `rev` (a branch, tag, or commit sha) to a concrete commit sha in
+/// `repo`. A full 40-char hex sha is pinned exactly; anything else is looked
+/// up as `origin/<rev>` first (branches), falling back to a bare ref name
+/// (tags, which aren't remote-tracked); with no `rev` at all, `origin/HEAD`
+/// is used.
+fn resolve_rev(repo: &Repository, rev: Option<&str>) -> Result<String> {
+    let candidates: Vec<String> = match rev {
+        Some(rev) if is_full_sha(rev) => vec![rev.to_string()],
+        Some(rev) => vec![format!("origin/{rev}"), rev.to_string()],
+        None => vec!["origin/HEAD".to_string()],
+    };
+    for candidate in candidates {
+        if let Ok(object) = repo.revparse_single(&candidate) {
+            let commit = object.peel_to_commit().map_err(Error::Git)?;
+            return Ok(commit.id().to_string());
+        }
+    }
+    Err(WebError::NotFound.into())
+}
+
+/// Picks the one `.wasm` file at the root of `commit`'s tree. Only the
+/// `.wasm` tier of [`super::repo::guess_repo_file_name`]'s heuristic applies
+/// here: a lone file of another extension, or a lone `.wat`, is not picked.
+fn guess_file_path(repo: &Repository, commit_id: &str) -> Result<String> {
+    let oid = Oid::from_str(commit_id).map_err(Error::Git)?;
+    let commit = repo.find_commit(oid).map_err(Error::Git)?;
+    let tree = commit.tree().map_err(Error::Git)?;
+    let mut files = tree
+        .iter()
+        .filter_map(|entry| entry.name().map(str::to_string))
+        .filter(|name| name.ends_with(".wasm"));
+    match (files.next(), files.next()) {
+        (Some(only), None) => Ok(only),
+        _ => Err(WebError::NotWasm.into()),
+    }
+}
+
+/// Reads `file_path` out of `rev`'s tree, returning `None` rather than an
+/// error if it simply isn't there (used for the optional `.sig` sibling).
+fn read_optional_blob(dir: &Path, rev: &str, file_path: &str) -> Result<Option<Vec<u8>>> {
+    let repo = Repository::open_bare(dir).map_err(Error::Git)?;
+    let oid = Oid::from_str(rev).map_err(Error::Git)?;
+    let commit = repo.find_commit(oid).map_err(Error::Git)?;
+    let tree = commit.tree().map_err(Error::Git)?;
+    let Ok(entry) = tree.get_path(Path::new(file_path)) else {
+        return Ok(None);
+    };
+    let object = entry.to_object(&repo).map_err(Error::Git)?;
+    let Some(blob) = object.as_blob() else {
+        return Ok(None);
+    };
+    Ok(Some(blob.content().to_vec()))
+}
+
+fn read_blob(dir: &Path, rev: &str, file_path: &str) -> Result<Vec<u8>> {
+    read_optional_blob(dir, rev, file_path)?.ok_or_else(|| WebError::NotFound.into())
+}
+
+pub(super) async fn resolve_git(url: &Url) -> Result<GitResolvedModule> {
+    let parsed = GitUrl::parse(url)?;
+    let user = parsed.user.clone();
+    let repo_name = parsed.repo.clone();
+    let canonical_url = parsed.canonical_url();
+    let short_hash = parsed.short_hash();
+    let clone_url = parsed.clone_url();
+    let rev = parsed.rev.clone();
+    let file_path = parsed.file_path.clone();
+    let dir = checkout_dir(&short_hash);
+
+    let (resolved_rev, file_path) = tokio::task::spawn_blocking(move || -> Result<(String, String)> {
+        let repo = open_and_fetch(&dir, &clone_url)?;
+        let resolved_rev = resolve_rev(&repo, rev.as_deref())?;
+        let file_path = match file_path {
+            Some(path) => path,
+            None => guess_file_path(&repo, &resolved_rev)?,
+        };
+        Ok((resolved_rev, file_path))
+    })
+    .await
+    .map_err(|_| Error::Wasm(anyhow::anyhow!("git checkout task panicked")))??;
+
+    Ok(GitResolvedModule {
+        canonical_url,
+        short_hash,
+        user,
+        repo: repo_name,
+        rev: resolved_rev,
+        file_path,
+        content: None,
+    })
+}
+
+pub(crate) async fn load_content(module: &mut ResolvedModule) -> Result<()> {
+    if module.content().is_some() {
+        return Ok(());
+    }
+    let resolved = module.downcast::<GitResolvedModule>();
+    let key = GitBlobKey {
+        short_hash: resolved.short_hash.clone(),
+        rev: resolved.rev.clone(),
+        file_path: resolved.file_path.clone(),
+    };
+    let dir = checkout_dir(&resolved.short_hash);
+    let rev = resolved.rev.clone();
+    let file_path = resolved.file_path.clone();
+    let content = cache::cached_git_blob(key, move || async move {
+        tokio::task::spawn_blocking(move || read_blob(&dir, &rev, &file_path))
+            .await
+            .map_err(|_| Error::Wasm(anyhow::anyhow!("git read task panicked")))?
+    })
+    .await?;
+    if !manifest::looks_like_wasm_module(&content) {
+        return Err(WebError::NotWasm.into());
+    }
+    resolved.set_content(content);
+    Ok(())
+}
+
+/// A module resolved from a [`GitLoader`] checkout. `rev` is always a
+/// concrete commit sha (never a branch/tag name), recorded here so a later
+/// `ensure_content` call re-reads the exact same blob even if the upstream
+/// ref has since moved.
+pub(crate) struct GitResolvedModule {
+    #[allow(dead_code)]
+    canonical_url: String,
+    short_hash: String,
+    user: String,
+    #[allow(dead_code)]
+    repo: String,
+    rev: String,
+    file_path: String,
+    content: Option<Vec<u8>>,
+}
+
+impl GitResolvedModule {
+    fn set_content<B: Into<Vec<u8>>>(&mut self, content: B) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content.into());
+    }
+}
+
+impl ResolverResult for GitResolvedModule {
+    fn domain(&self) -> Domain {
+        Domain::Git
+    }
+
+    fn user(&self) -> &str {
+        &self.user
+    }
+
+    fn name(&self) -> &str {
+        &self.file_path
+    }
+
+    fn cache_identity(&self) -> String {
+        format!("git:{}/{}/{}", self.short_hash, self.rev, self.file_path)
+    }
+
+    fn content(&self) -> Option<&[u8]> {
+        self.content.as_deref()
+    }
+
+    fn take_content(&mut self) -> Option<Vec<u8>> {
+        self.content.take()
+    }
+
+    fn set_content(&mut self, content: Vec<u8>) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Resolves modules from arbitrary Git hosts via `git+https://`/`git+http://`
+/// urls, as opposed to [`super::repo::RepoLoader`]'s GitHub-only REST API
+/// integration.
+pub(super) struct GitLoader;
+
+impl WebLoader for GitLoader {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        matches!(
+            origin,
+            Origin::Tuple(scheme, ..) if scheme == "git+https" || scheme == "git+http"
+        )
+    }
+
+    /// Cloning and fetching a whole repository is much heavier than the
+    /// single HTTP round-trip the default budget assumes.
+    fn fetch_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(120)
+    }
+
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>> {
+        Box::pin(async move {
+            let resolved = resolve_git(url).await?;
+            Ok(Box::new(resolved) as Box<dyn ResolverResult + Send + Sync>)
+        })
+    }
+
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>> {
+        Box::pin(load_content(module))
+    }
+
+    fn fetch_signature<'a>(
+        &'a self,
+        module: &'a ResolvedModule,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let resolved = module.downcast_ref::<GitResolvedModule>();
+            let dir = checkout_dir(&resolved.short_hash);
+            let rev = resolved.rev.clone();
+            let sig_path = format!("{}.sig", resolved.file_path);
+            tokio::task::spawn_blocking(move || read_optional_blob(&dir, &rev, &sig_path))
+                .await
+                .map_err(|_| Error::Wasm(anyhow::anyhow!("git read task panicked")))?
+        })
+    }
+}
@@ -0,0 +1,70 @@
+//! Rewrites short bare specifiers and aliases to the trusted urls they
+//! should actually be fetched from, before [`super::resolve`] dispatches to
+//! a loader and checks the result's origin against the registered set.
+//!
+//! Modeled loosely on [Deno's import maps][import-maps]: an exact-key entry
+//! aliases one specifier to one url, while a trailing-slash key remaps any
+//! specifier sharing that prefix, appending the remainder of the path to
+//! the mapped target. Neither kind of entry is itself a trust decision — the
+//! origin allow-list consulted by `resolve` is still the only thing that
+//! decides whether a rewritten url is actually fetched from.
+//!
+//! [import-maps]: https://github.com/WICG/import-maps
+
+use std::collections::HashMap;
+
+use url::Url;
+
+use super::InvalidUrl;
+use crate::service::Result;
+
+/// A set of specifier rewrites, consulted before origin dispatch.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImportMap {
+    imports: HashMap<String, String>,
+}
+
+impl ImportMap {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a rewrite. A `specifier` ending in `/` remaps any bare
+    /// specifier sharing that prefix; otherwise it's an exact-match alias.
+    pub(crate) fn insert(&mut self, specifier: impl Into<String>, target: impl Into<String>) {
+        self.imports.insert(specifier.into(), target.into());
+    }
+
+    /// Rewrites `specifier` to the url it should actually be loaded from:
+    /// an exact alias, the longest matching prefix remap, a url already
+    /// parseable on its own, or (failing all of those) a relative reference
+    /// resolved against `referrer`.
+    pub(crate) fn resolve(&self, specifier: &str, referrer: Option<&Url>) -> Result<Url> {
+        if let Some(target) = self.imports.get(specifier) {
+            let url: Url = target.parse().map_err(|_| InvalidUrl::ParseError)?;
+            return Ok(url);
+        }
+        if let Some((prefix, target)) = self.longest_prefix_match(specifier) {
+            let rewritten = format!("{target}{}", &specifier[prefix.len()..]);
+            let url: Url = rewritten.parse().map_err(|_| InvalidUrl::ParseError)?;
+            return Ok(url);
+        }
+        if let Ok(url) = specifier.parse::<Url>() {
+            return Ok(url);
+        }
+        let referrer = referrer.ok_or(InvalidUrl::ParseError)?;
+        let url = referrer.join(specifier).map_err(|_| InvalidUrl::ParseError)?;
+        Ok(url)
+    }
+
+    /// The longest `/`-suffixed key that prefixes `specifier`, if any, along
+    /// with its mapped target. Longest match wins so a more specific prefix
+    /// (e.g. `"trusted/chatbots/"`) overrides a broader one (`"trusted/"`).
+    fn longest_prefix_match(&self, specifier: &str) -> Option<(&str, &str)> {
+        self.imports
+            .iter()
+            .filter(|(key, _)| key.ends_with('/') && specifier.starts_with(key.as_str()))
+            .map(|(key, target)| (key.as_str(), target.as_str()))
+            .max_by_key(|(key, _)| key.len())
+    }
+}
@@ -0,0 +1,121 @@
+//! Subresource-integrity digests, modeled on npm's `integrity` field (itself
+//! modeled on the [SRI](https://www.w3.org/TR/SRI/) `<alg>-<base64>` string):
+//! one or more `(algorithm, digest)` pairs a fetched module's bytes can be
+//! checked against, so a resolver that already knows what it expects (e.g. a
+//! pinned [`super::gistlock`] entry) can catch upstream content changing out
+//! from under it instead of silently loading whatever comes back.
+
+use std::fmt;
+use std::str::FromStr;
+
+use base64::Engine as _;
+use sha2::{Digest as _, Sha256, Sha512};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+
+    fn digest(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+            Algorithm::Sha512 => Sha512::digest(bytes).to_vec(),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Algorithm::Sha256),
+            "sha512" => Ok(Algorithm::Sha512),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One `<algorithm>-<base64(digest)>` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Digest {
+    algorithm: Algorithm,
+    value: Vec<u8>,
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.algorithm.as_str(),
+            base64::engine::general_purpose::STANDARD.encode(&self.value)
+        )
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, value) = s.split_once('-').ok_or(())?;
+        let algorithm = algorithm.parse().map_err(|_| ())?;
+        let value = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|_| ())?;
+        Ok(Self { algorithm, value })
+    }
+}
+
+/// One or more digests a module's content should satisfy. Content passes if
+/// it matches at least one entry, whatever that entry's algorithm; it fails
+/// only when every entry mismatches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Integrity(Vec<Digest>);
+
+impl Integrity {
+    /// The sha256 digest of `bytes`, as a single-entry [`Integrity`].
+    pub(super) fn compute_sha256(bytes: &[u8]) -> Self {
+        Self(vec![Digest {
+            algorithm: Algorithm::Sha256,
+            value: Algorithm::Sha256.digest(bytes),
+        }])
+    }
+
+    /// Whether `bytes` matches at least one of this integrity's digests.
+    pub(super) fn matches(&self, bytes: &[u8]) -> bool {
+        self.0
+            .iter()
+            .any(|digest| digest.value == digest.algorithm.digest(bytes))
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered: Vec<String> = self.0.iter().map(Digest::to_string).collect();
+        write!(f, "{}", rendered.join(" "))
+    }
+}
+
+impl FromStr for Integrity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digests: Result<Vec<Digest>, ()> = s.split_whitespace().map(Digest::from_str).collect();
+        let digests = digests?;
+        if digests.is_empty() {
+            Err(())
+        } else {
+            Ok(Self(digests))
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Parsing of an embedded module manifest from a custom wasm section.
+//!
+//! A module may declare a `wotto-manifest` custom section containing a small
+//! JSON document describing which host protocol version it was built
+//! against, which host capabilities it requires, and (optionally) a JSON
+//! Schema describing the runtime configuration it expects. This lets the
+//! runtime reject modules that are not compatible before instantiating them.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use semver::Version;
+use serde::Deserialize;
+use thiserror::Error;
+
+const MANIFEST_SECTION_NAME: &str = "wotto-manifest";
+const WASM_MAGIC: [u8; 4] = *b"\0asm";
+const WASM_VERSION: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+
+#[derive(Debug, Error)]
+pub(crate) enum ManifestError {
+    #[error("not a valid wasm module")]
+    InvalidModule,
+    #[error("manifest section is not valid json")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("manifest declares an invalid version: {0}")]
+    InvalidVersion(#[from] semver::Error),
+}
+
+/// The `wotto-manifest` custom section, if a module declares one.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ModuleManifest {
+    version: String,
+    #[serde(default)]
+    capabilities: BTreeSet<String>,
+    #[serde(rename = "configSchema", default)]
+    config_schema: Option<serde_json::Value>,
+}
+
+impl ModuleManifest {
+    pub(crate) fn version(&self) -> Result<Version, semver::Error> {
+        Version::parse(&self.version)
+    }
+
+    pub(crate) fn capabilities(&self) -> &BTreeSet<String> {
+        &self.capabilities
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn config_schema(&self) -> Option<&serde_json::Value> {
+        self.config_schema.as_ref()
+    }
+}
+
+/// Read an unsigned LEB128 integer starting at `*pos`, advancing `*pos` past it.
+fn read_leb128(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut result: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+        shift += 7;
+        if shift >= 32 {
+            return None;
+        }
+    }
+}
+
+/// If `bytes` is not already a wasm binary, assume it's WAT text and assemble
+/// it, so the caller can scan sections regardless of the input format.
+fn to_wasm_bytes(bytes: &[u8]) -> Result<Cow<'_, [u8]>, ManifestError> {
+    if bytes.starts_with(&WASM_MAGIC) {
+        Ok(Cow::Borrowed(bytes))
+    } else {
+        wat::parse_bytes(bytes)
+            .map(|cow| Cow::Owned(cow.into_owned()))
+            .map_err(|_| ManifestError::InvalidModule)
+    }
+}
+
+/// Cheaply confirm that `bytes` is plausibly a wasm module, without fully
+/// validating it: binary modules are checked against the magic/version
+/// preamble, text modules are accepted if they parse as WAT.
+pub(crate) fn looks_like_wasm_module(bytes: &[u8]) -> bool {
+    if bytes.starts_with(&WASM_MAGIC) {
+        bytes.len() >= 8 && bytes[4..8] == WASM_VERSION
+    } else {
+        wat::parse_bytes(bytes).is_ok()
+    }
+}
+
+/// Scan a wasm module's sections for a `wotto-manifest` custom section and
+/// parse it, if present.
+pub(crate) fn parse_manifest(bytes: &[u8]) -> Result<Option<ModuleManifest>, ManifestError> {
+    let bytes = to_wasm_bytes(bytes)?;
+    if bytes.len() < 8 || bytes[0..4] != WASM_MAGIC || bytes[4..8] != WASM_VERSION {
+        return Err(ManifestError::InvalidModule);
+    }
+
+    let mut pos = 8;
+    while pos < bytes.len() {
+        let id = bytes[pos];
+        pos += 1;
+        let size = read_leb128(&bytes, &mut pos).ok_or(ManifestError::InvalidModule)? as usize;
+        let section_end = pos.checked_add(size).ok_or(ManifestError::InvalidModule)?;
+        if section_end > bytes.len() {
+            return Err(ManifestError::InvalidModule);
+        }
+        let section = &bytes[pos..section_end];
+
+        if id == 0 {
+            let mut name_pos = 0;
+            let name_len =
+                read_leb128(section, &mut name_pos).ok_or(ManifestError::InvalidModule)? as usize;
+            let name_end = name_pos
+                .checked_add(name_len)
+                .ok_or(ManifestError::InvalidModule)?;
+            if name_end > section.len() {
+                return Err(ManifestError::InvalidModule);
+            }
+            let name = &section[name_pos..name_end];
+            if name == MANIFEST_SECTION_NAME.as_bytes() {
+                let manifest: ModuleManifest = serde_json::from_slice(&section[name_end..])?;
+                // validate the version eagerly so callers get a clear error
+                // at load time rather than whenever they happen to inspect it
+                manifest.version()?;
+                return Ok(Some(manifest));
+            }
+        }
+
+        pos = section_end;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `n` as unsigned LEB128. Only used by tests, and only ever
+    /// with small values, so a single-byte fast path would do, but this
+    /// stays correct for anything a test throws at it.
+    fn leb128(mut n: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+        bytes
+    }
+
+    /// Assembles a minimal wasm binary with one custom section named `name`
+    /// carrying `payload`.
+    fn module_with_custom_section(name: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut section = leb128(name.len() as u32);
+        section.extend_from_slice(name);
+        section.extend_from_slice(payload);
+
+        let mut module = Vec::new();
+        module.extend_from_slice(&WASM_MAGIC);
+        module.extend_from_slice(&WASM_VERSION);
+        module.push(0); // custom section id
+        module.extend(leb128(section.len() as u32));
+        module.extend(section);
+        module
+    }
+
+    #[test]
+    fn round_trips_a_well_formed_manifest() {
+        let json = br#"{"version":"1.2.3","capabilities":["net","fs"]}"#;
+        let module = module_with_custom_section(MANIFEST_SECTION_NAME.as_bytes(), json);
+
+        let manifest = parse_manifest(&module).unwrap().expect("manifest section present");
+        assert_eq!(manifest.version().unwrap(), Version::new(1, 2, 3));
+        assert_eq!(
+            manifest.capabilities(),
+            &BTreeSet::from(["net".to_string(), "fs".to_string()])
+        );
+    }
+
+    #[test]
+    fn rejects_a_truncated_section_length() {
+        let mut module = Vec::new();
+        module.extend_from_slice(&WASM_MAGIC);
+        module.extend_from_slice(&WASM_VERSION);
+        module.push(0); // custom section id
+        module.push(0x80); // LEB128 continuation byte with nothing to continue into
+
+        assert!(matches!(
+            parse_manifest(&module),
+            Err(ManifestError::InvalidModule)
+        ));
+    }
+
+    #[test]
+    fn ignores_a_similarly_named_section() {
+        // a section whose name merely starts with the manifest section's
+        // name must not be mistaken for it.
+        let mut name = MANIFEST_SECTION_NAME.as_bytes().to_vec();
+        name.push(b'x');
+        let module = module_with_custom_section(&name, br#"{"version":"1.0.0"}"#);
+
+        assert!(parse_manifest(&module).unwrap().is_none());
+    }
+}
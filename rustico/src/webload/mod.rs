@@ -1,14 +1,40 @@
+mod auth;
+mod cache;
+mod credentials;
 mod gist;
+mod gistgit;
+mod gistlock;
+mod git;
+mod importmap;
+mod integrity;
+mod manifest;
+mod rawgithub;
+mod repo;
+mod signing;
+mod singlefile;
+mod urlcache;
 
 use std::any::Any;
-use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use futures::future::{AbortRegistration, Abortable};
 use lazy_static::lazy_static;
+use rusto_utils::escape::escape_str;
 use thiserror::Error;
 use url::{Origin, Url};
 
 use crate::service::Result;
 
+pub(crate) use auth::trust_credentials;
+pub(crate) use importmap::ImportMap;
+pub(crate) use manifest::{parse_manifest, ManifestError, ModuleManifest};
+pub(crate) use signing::trust_key;
+pub(crate) use singlefile::SingleFileLoader;
+pub(crate) use urlcache::CacheKey;
+
 #[derive(Error, Debug)]
 pub enum InvalidUrl {
     #[error("url cannot be parsed")]
@@ -33,21 +59,264 @@ pub enum WebError {
     TooLarge,
     #[error("missing credentials")]
     NoCredentials,
+    #[error("invalid module manifest: {0}")]
+    InvalidManifest(#[from] manifest::ManifestError),
+    #[error("not authorized (bad or missing credentials)")]
+    Unauthorized,
+    #[error("not found")]
+    NotFound,
+    #[error("rate limited, reset at {reset_at:?}")]
+    RateLimited { reset_at: Option<u64> },
+    #[error("unexpected response from the repository host's api")]
+    UnexpectedResponse,
+    #[error("module signature is missing or untrusted")]
+    UntrustedSignature,
+    #[error("timed out waiting for the module source")]
+    Timeout,
+    #[error("load was cancelled")]
+    Cancelled,
+    #[error("fetched content does not match its expected integrity digest")]
+    IntegrityMismatch,
+    #[error("failed to persist lock file: {0}")]
+    LockfileIo(#[from] std::io::Error),
+}
+
+/// Modules larger than this are rejected without being fully buffered.
+const MAX_MODULE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Whether `s` already looks like a full git commit sha, as opposed to a
+/// branch or tag name, shared by [`repo`] and [`git`] since both need to
+/// tell "pin to this exact commit" apart from "resolve this ref".
+pub(super) fn is_full_sha(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Default budget for a single `WebLoader::load`/`load_content` call before
+/// it's abandoned with [`WebError::Timeout`]. Loaders whose fetch is
+/// unusually heavy (e.g. [`git::GitLoader`] cloning a whole repository)
+/// should override [`WebLoader::fetch_timeout`] rather than let slow hosts
+/// hang the caller indefinitely.
+const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Runs `fut` under `timeout`, and — if `cancellation` is given — lets an
+/// external [`futures::future::AbortHandle`] cut it short too. Shared by
+/// [`resolve_cancellable`] and [`ResolvedModule::ensure_content_cancellable`],
+/// the two places a loader's I/O can hang: resolving a module's identity and
+/// fetching its content.
+async fn run_cancellable<T>(
+    timeout: Duration,
+    cancellation: Option<AbortRegistration>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let timed = tokio::time::timeout(timeout, fut);
+    let outcome = match cancellation {
+        Some(registration) => Abortable::new(timed, registration)
+            .await
+            .map_err(|_| WebError::Cancelled)?,
+        None => timed.await,
+    };
+    outcome.map_err(|_| WebError::Timeout)?
+}
+
+/// An HTTP client shared by loaders that talk to github.com and its
+/// associated raw-content hosts.
+///
+/// Redirects are followed automatically, but each hop's origin is checked
+/// against the registered [`WebLoader`]s first — a redirect can't smuggle a
+/// fetch started for a trusted origin off to one nothing would otherwise
+/// resolve from. A rejected hop surfaces as a [`reqwest::Error`] (wrapping
+/// [`InvalidUrl::RejectedOrigin`]), since that's the only way
+/// [`reqwest::redirect::Policy::custom`] can abort a redirect.
+fn client() -> Result<reqwest::Client> {
+    Ok(reqwest::ClientBuilder::new()
+        .user_agent("https://github.com/sorcio/rusto")
+        .https_only(true)
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+            if REGISTRY.lock().unwrap().find(&attempt.url().origin()).is_some() {
+                attempt.follow()
+            } else {
+                attempt.error(InvalidUrl::RejectedOrigin)
+            }
+        }))
+        .build()
+        .map_err(WebError::ReqwestError)?)
+}
+
+/// Drain `response` into memory, aborting with [`WebError::TooLarge`] the
+/// moment the cumulative size (or the advertised `Content-Length`) exceeds
+/// `limit`, instead of buffering an unbounded body up front.
+async fn download_with_limit(mut response: reqwest::Response, limit: u64) -> Result<Vec<u8>> {
+    if response.content_length().map_or(false, |len| len > limit) {
+        return Err(WebError::TooLarge.into());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(WebError::TemporaryFailure)? {
+        if body.len() as u64 + chunk.len() as u64 > limit {
+            return Err(WebError::TooLarge.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Fetches the detached signature sibling of `raw_url` (`<raw_url>.sig`),
+/// returning `None` rather than an error if the host doesn't have one —
+/// most modules aren't signed, and the caller only turns a missing
+/// signature into [`WebError::UntrustedSignature`] once a key is actually
+/// configured for that module's domain/user.
+async fn fetch_detached_signature(raw_url: &str) -> Result<Option<Vec<u8>>> {
+    let sig_url = format!("{raw_url}.sig");
+    let Ok(parsed_sig_url) = sig_url.parse::<Url>() else {
+        return Ok(None);
+    };
+    let origin = parsed_sig_url.origin();
+    let client = client()?;
+    // A 401 here (an internal mirror requiring auth for its raw content)
+    // is retried with whatever credentials are trusted for this origin; one
+    // from an origin with nothing on file just means no signature, same as
+    // any other non-success status.
+    let response = match auth::execute_with_auth(&client, &origin, || client.get(&sig_url)).await {
+        Ok(response) => response,
+        Err(_) => return Ok(None),
+    };
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    Ok(Some(download_with_limit(response, 4096).await?))
 }
 
 trait ResolverResult {
     fn domain(&self) -> Domain;
     fn user(&self) -> &str;
     fn name(&self) -> &str;
+    /// A stable identity for this module's content, used to key the
+    /// top-level cache in [`urlcache`] so differently-spelled urls that
+    /// resolve to the same content (`gist.github.com` vs
+    /// `gist.githubusercontent.com`, a repo's `blob` vs `raw` form, a
+    /// trailing `#file-...` fragment) share one cache entry. Unlike
+    /// `domain()`/`user()`/`name()` alone, this also folds in whatever pins
+    /// the content exactly (a blob/commit sha) where the loader has one. A
+    /// raw-github branch ref or a single-file url path does not pin it, so a
+    /// cached entry for those can outlive an upstream change until it expires.
+    fn cache_identity(&self) -> String;
     fn content(&self) -> Option<&[u8]>;
     fn take_content(&mut self) -> Option<Vec<u8>>;
+    /// Fills in content fetched by something other than this loader's own
+    /// `load_content`, namely the canonical-url cache in [`urlcache`].
+    fn set_content(&mut self, content: Vec<u8>);
+    fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
+/// Result of a `WebLoader::load` or `load_content` call, boxed so the
+/// registry can be generic over loader implementations without `async fn`
+/// in a trait object.
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A source of wasm modules for a set of url origins.
+///
+/// Implement this trait and call [`register_loader`] to teach `webload` how
+/// to fetch modules from a new host, without touching `resolve()` itself.
+pub(crate) trait WebLoader: Send + Sync {
+    /// Short identifier used for debugging/logging.
+    fn name(&self) -> &'static str;
+
+    /// Whether this loader is responsible for fetching from `origin`.
+    fn matches(&self, origin: &Origin) -> bool;
+
+    /// Resolve enough information about the module at `url` to identify it
+    /// (user/name), fetching content eagerly only if that's the cheapest way
+    /// to do so.
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>>;
+
+    /// Make sure `module`'s content is populated, fetching it if necessary.
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>>;
+
+    /// Fetch `module`'s detached signature artifact, if this loader's host
+    /// publishes one alongside the module's content. Defaults to "never
+    /// signed"; override when the host has an obvious place to look (e.g. a
+    /// `.sig` sibling of the raw content url).
+    fn fetch_signature<'a>(
+        &'a self,
+        _module: &'a ResolvedModule,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Budget for a single `load`/`load_content` call before it's abandoned
+    /// with [`WebError::Timeout`]. Defaults to [`DEFAULT_FETCH_TIMEOUT`],
+    /// which suits a single HTTP round-trip; override for loaders whose
+    /// fetch is heavier.
+    fn fetch_timeout(&self) -> Duration {
+        DEFAULT_FETCH_TIMEOUT
+    }
+}
+
+/// Set of registered [`WebLoader`]s, consulted by [`resolve`] in registration
+/// order. This replaces what used to be a hardcoded `if origin == ... else if
+/// ...` chain in `find_loader`.
+pub(crate) struct LoaderRegistry {
+    loaders: Vec<Arc<dyn WebLoader>>,
+}
+
+impl LoaderRegistry {
+    fn with_defaults() -> Self {
+        let mut registry = Self { loaders: Vec::new() };
+        registry.register(Arc::new(gist::GistLoader));
+        registry.register(Arc::new(repo::RepoLoader));
+        registry.register(Arc::new(git::GitLoader));
+        registry.register(Arc::new(rawgithub::RawGithubLoader));
+        registry
+    }
+
+    pub(crate) fn register(&mut self, loader: Arc<dyn WebLoader>) {
+        self.loaders.push(loader);
+    }
+
+    fn find(&self, origin: &Origin) -> Option<Arc<dyn WebLoader>> {
+        self.loaders
+            .iter()
+            .find(|loader| loader.matches(origin))
+            .cloned()
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<LoaderRegistry> = Mutex::new(LoaderRegistry::with_defaults());
+}
+
+/// Register a loader for origins not already handled by the built-in set
+/// (GitLab snippets, or a bare raw-HTTPS allowlist via [`SingleFileLoader`]).
+#[allow(dead_code)]
+pub(crate) fn register_loader(loader: Arc<dyn WebLoader>) {
+    REGISTRY.lock().unwrap().register(loader);
+}
+
 pub(crate) struct ResolvedModule {
-    loader: Loader,
+    loader: Arc<dyn WebLoader>,
     url: Url,
     resolved: Box<dyn ResolverResult + Send + Sync>,
+    /// The id of the key whose signature was verified over this module's
+    /// content, once [`Self::ensure_content`] has run. `None` either because
+    /// content hasn't been fetched yet, or because no key is configured for
+    /// this module's domain/user and so nothing was checked.
+    signature_key_id: Option<String>,
+    /// The url content was actually served from, once [`Self::ensure_content`]
+    /// has followed whatever redirects the host issued — may differ from
+    /// [`Self::url`] (the url that was asked for), mirroring Deno's
+    /// "specified" vs "found" module url. `None` until content is fetched,
+    /// and stays `None` for loaders (like [`git::GitLoader`]) that don't go
+    /// through an HTTP redirect at all.
+    resolved_url: Option<Url>,
+    /// This module's [`CacheKey`], computed from the loader's resolved
+    /// [`ResolverResult::cache_identity`] rather than [`Self::url`] directly,
+    /// so differently-spelled urls for the same content share one entry.
+    /// Lets a caller invalidate or persist the entry
+    /// [`Self::ensure_content_cancellable`] reads from and writes to.
+    cache_key: CacheKey,
 }
 
 impl ResolvedModule {
@@ -55,6 +324,28 @@ impl ResolvedModule {
         &self.url
     }
 
+    /// The cache key content fetched for this module is (or would be)
+    /// stored under in the canonical-url content cache.
+    #[allow(dead_code)]
+    pub(crate) fn cache_key(&self) -> &CacheKey {
+        &self.cache_key
+    }
+
+    /// The url content was actually fetched from, following any redirects.
+    /// `None` until [`Self::ensure_content`] has run, or for loaders that
+    /// have no notion of a redirected url.
+    #[allow(dead_code)]
+    pub(crate) fn resolved_url(&self) -> Option<&Url> {
+        self.resolved_url.as_ref()
+    }
+
+    /// Records the url content was actually served from, once a loader's
+    /// `load_content` has followed redirects. Private like [`Self::downcast`]
+    /// — only loader implementations in submodules need to call this.
+    fn set_resolved_url(&mut self, url: Url) {
+        self.resolved_url = Some(url);
+    }
+
     pub(crate) fn domain(&self) -> Domain {
         self.resolved.domain()
     }
@@ -71,8 +362,83 @@ impl ResolvedModule {
         self.resolved.content()
     }
 
+    /// The SHA-256 digest of this module's content, once [`Self::ensure_content`]
+    /// has populated it. Keys the compiled-module cache in [`crate::service`]
+    /// so the same bytes resolved under a different fqn, or re-fetched after a
+    /// restart, reuse the compiled artifact instead of paying to recompile.
+    pub(crate) fn content_digest(&self) -> Option<String> {
+        self.content().map(crate::lockfile::digest)
+    }
+
+    /// The id of the key that signed this module, if its domain/user has a
+    /// trusted key configured and [`Self::ensure_content`] has verified the
+    /// signature against it. Lets callers display provenance.
+    #[allow(dead_code)]
+    pub(crate) fn signature_key_id(&self) -> Option<&str> {
+        self.signature_key_id.as_deref()
+    }
+
+    /// The manifest the module declares about itself, if `ensure_content` has
+    /// already populated the module's bytes and it carries a `wotto-manifest`
+    /// custom section.
+    #[allow(dead_code)]
+    pub(crate) fn manifest(&self) -> Result<Option<ModuleManifest>> {
+        let Some(content) = self.content() else {
+            return Ok(None);
+        };
+        Ok(manifest::parse_manifest(content).map_err(WebError::InvalidManifest)?)
+    }
+
     pub(crate) async fn ensure_content(&mut self) -> Result<()> {
-        self.loader.ensure_content(self).await
+        self.ensure_content_cancellable(None).await
+    }
+
+    /// As [`Self::ensure_content`], but lets `cancellation` (the
+    /// [`AbortRegistration`] half of an [`futures::future::AbortHandle`]
+    /// pair) cut the fetch short from outside, in addition to the
+    /// per-loader [`WebLoader::fetch_timeout`] that always applies.
+    pub(crate) async fn ensure_content_cancellable(
+        &mut self,
+        cancellation: Option<AbortRegistration>,
+    ) -> Result<()> {
+        if self.content().is_some() {
+            return Ok(());
+        }
+        if let Some(content) = urlcache::get(&self.cache_key) {
+            self.resolved.set_content(content);
+            return self.verify_signature().await;
+        }
+        let loader = self.loader.clone();
+        let timeout = loader.fetch_timeout();
+        run_cancellable(timeout, cancellation, loader.load_content(self)).await?;
+        if let Some(content) = self.content() {
+            urlcache::insert(self.cache_key.clone(), content.to_vec());
+        }
+        self.verify_signature().await
+    }
+
+    /// Checks `self`'s content against whatever key(s) are trusted for its
+    /// `(domain, user)`, if any. A module whose domain/user has no
+    /// registered key is left unsigned (nothing to enforce); one that does
+    /// must present a valid, matching signature or the load fails.
+    async fn verify_signature(&mut self) -> Result<()> {
+        let domain = self.domain();
+        let user = self.user().to_string();
+        if !signing::has_keys(domain, &user) {
+            return Ok(());
+        }
+        let name = self.name().to_string();
+        let loader = self.loader.clone();
+        let signature = loader
+            .fetch_signature(&*self)
+            .await?
+            .ok_or(WebError::UntrustedSignature)?;
+        let content = self
+            .content()
+            .expect("load_content just populated content");
+        let key_id = signing::verify(domain, &user, &self.url, &name, content, &signature)?;
+        self.signature_key_id = Some(key_id);
+        Ok(())
     }
 
     fn downcast<T: ResolverResult + 'static>(&mut self) -> &mut T {
@@ -82,94 +448,76 @@ impl ResolvedModule {
             .expect("downcast should be only called when the concrete type is known")
     }
 
+    fn downcast_ref<T: ResolverResult + 'static>(&self) -> &T {
+        self.resolved
+            .as_any()
+            .downcast_ref()
+            .expect("downcast_ref should be only called when the concrete type is known")
+    }
 }
 
 impl core::fmt::Debug for ResolvedModule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ResolvedModule")
-            .field("loader", &self.loader)
+            .field("loader", &self.loader.name())
             .field("url", &self.url)
             .field("domain", &self.domain())
-            .field("user", &self.user())
-            .field("name", &self.name())
+            .field("user", &escape_str(self.user()))
+            .field("name", &escape_str(self.name()))
             .field("has_content", &self.content().is_some())
+            .field("signature_key_id", &self.signature_key_id)
+            .field("resolved_url", &self.resolved_url)
+            .field("cache_key", &self.cache_key)
             .finish()
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Loader {
-    Gist,
-}
-
-impl Loader {
-    fn from_url(url: &Url) -> Result<Self> {
-        ORIGIN_MAP
-            .get(&url.origin())
-            .ok_or(InvalidUrl::RejectedOrigin.into())
-            .copied()
-    }
-
-    async fn resolve(self, url: Url) -> Result<ResolvedModule> {
-        let resolved = match self {
-            Loader::Gist => gist::resolve_gist(&url).await?,
-        };
-        Ok(ResolvedModule {
-            loader: self,
-            url,
-            resolved: Box::new(resolved),
-        })
-    }
-
-    async fn ensure_content(self, module: &mut ResolvedModule) -> Result<()> {
-        if module.content().is_some() {
-            return Ok(());
-        }
-        match self {
-            Loader::Gist => gist::load_content(module).await,
-        }
-    }
-}
-
-/// Internal (used by Loader)
-macro_rules! origin_map {
-    {$($url:literal => $target:expr),* $(,)?} => {
-        {
-            use ::std::collections::HashMap;
-            let mut origin_map = HashMap::new();
-            $(
-                origin_map.insert(
-                    $url.parse::<Url>().unwrap().origin(),
-                    $target
-                );
-            )*
-            origin_map
-        }
-    };
-}
-
-lazy_static! {
-    /// Internal (used by Loader)
-    static ref ORIGIN_MAP: HashMap<Origin, Loader> = origin_map!{
-        "https://gist.github.com/" => Loader::Gist,
-        "https://gist.githubusercontent.com/" => Loader::Gist
-    };
-}
-
 /// Domain defines the domain for the user, in case one day we want to have a
 /// more complex namespacing scheme, or code authentication. E.g.
 /// `Domain::Github` indicates that the user (in `WebModule`) is a GitHub user.
 #[non_exhaustive]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum Domain {
     Github,
+    /// A user on an arbitrary Git host, loaded via [`git::GitLoader`] rather
+    /// than the GitHub-specific [`repo::RepoLoader`].
+    Git,
     #[allow(dead_code)]
     Builtin,
-    #[allow(dead_code)]
     Other(&'static str),
 }
 
 pub(crate) async fn resolve(url: Url) -> Result<ResolvedModule> {
-    let loader = Loader::from_url(&url)?;
-    loader.resolve(url).await
+    resolve_cancellable(url, None).await
+}
+
+/// As [`resolve`], but lets `cancellation` (the [`AbortRegistration`] half
+/// of an [`futures::future::AbortHandle`] pair) cut the fetch short from
+/// outside, in addition to the per-loader [`WebLoader::fetch_timeout`] that
+/// always applies.
+pub(crate) async fn resolve_cancellable(
+    url: Url,
+    cancellation: Option<AbortRegistration>,
+) -> Result<ResolvedModule> {
+    if !url.username().is_empty() || url.password().is_some() {
+        // Credentials are resolved per-origin via `trust_credentials`, never
+        // embedded in the module url itself.
+        return Err(InvalidUrl::CredentialsNotAllowed.into());
+    }
+    let loader = REGISTRY
+        .lock()
+        .unwrap()
+        .find(&url.origin())
+        .ok_or(InvalidUrl::RejectedOrigin)?;
+    let timeout = loader.fetch_timeout();
+    let resolved = run_cancellable(timeout, cancellation, loader.load(&url)).await?;
+    let cache_key = urlcache::cache_key(&resolved.cache_identity());
+    Ok(ResolvedModule {
+        loader,
+        url,
+        resolved,
+        signature_key_id: None,
+        resolved_url: None,
+        cache_key,
+    })
 }
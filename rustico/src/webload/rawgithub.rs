@@ -0,0 +1,216 @@
+use lazy_static::lazy_static;
+use url::{Origin, Url};
+
+use super::credentials::{execute_with_retry, Credentials};
+use super::{
+    client, download_with_limit, manifest, BoxFuture, Domain, InvalidUrl, ResolvedModule,
+    ResolverResult, WebError, WebLoader, MAX_MODULE_SIZE,
+};
+use crate::service::Result;
+
+/// Parsed `raw.githubusercontent.com/<user>/<repo>/<ref>/<path>` url. Unlike
+/// [`super::repo::RepoLoader`], which sees a `github.com/<user>/<repo>` url
+/// (bare, `blob` or `raw`) and resolves its ref to a commit sha via the API
+/// first, this loader
+/// takes the raw url's ref literally — there's no sha to pin here, so
+/// re-fetching a branch-named ref can observe a different commit over time.
+struct RawGithub<'a> {
+    user: &'a str,
+    repo: &'a str,
+    git_ref: &'a str,
+    file_path: &'a str,
+}
+
+impl<'a> RawGithub<'a> {
+    fn parse(url: &'a Url) -> Result<Self> {
+        let trimmed = url.path().trim_matches('/');
+        let mut parts = trimmed.splitn(4, '/');
+        let user = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        let repo = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        let git_ref = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        let file_path = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        Ok(Self {
+            user,
+            repo,
+            git_ref,
+            file_path,
+        })
+    }
+}
+
+pub(super) async fn resolve_raw_github(url: &Url) -> Result<impl ResolverResult> {
+    debug_assert_eq!(url.scheme(), "https");
+    debug_assert!(matches!(
+        url.host(),
+        Some(url::Host::Domain("raw.githubusercontent.com"))
+    ));
+
+    let parsed = RawGithub::parse(url)?;
+    Ok(RawGithubResolvedModule {
+        user: parsed.user.to_string(),
+        repo: parsed.repo.to_string(),
+        git_ref: parsed.git_ref.to_string(),
+        file_path: parsed.file_path.to_string(),
+        content: None,
+    })
+}
+
+pub(crate) async fn load_content(module: &mut ResolvedModule) -> Result<()> {
+    if module.content().is_some() {
+        return Ok(());
+    }
+    let resolved = module.downcast::<RawGithubResolvedModule>();
+    let fetch_url = resolved.build_raw_url();
+
+    let client = client()?;
+    let credentials = Credentials::resolve("github");
+    let response = execute_with_retry(&client, || {
+        let request = client.request(reqwest::Method::GET, &fetch_url);
+        match &credentials {
+            Some(credentials) => credentials.apply(request),
+            None => request,
+        }
+    })
+    .await?;
+    let resolved_url = response.url().clone();
+    let content = download_with_limit(response, MAX_MODULE_SIZE).await?;
+    if !manifest::looks_like_wasm_module(&content) {
+        return Err(WebError::NotWasm.into());
+    }
+    resolved.set_content(content);
+    module.set_resolved_url(resolved_url);
+    Ok(())
+}
+
+struct RawGithubResolvedModule {
+    user: String,
+    repo: String,
+    git_ref: String,
+    file_path: String,
+    content: Option<Vec<u8>>,
+}
+
+impl RawGithubResolvedModule {
+    fn build_raw_url(&self) -> String {
+        let Self {
+            user,
+            repo,
+            git_ref,
+            file_path,
+            ..
+        } = self;
+        format!("https://raw.githubusercontent.com/{user}/{repo}/{git_ref}/{file_path}")
+    }
+
+    fn set_content<B: Into<Vec<u8>>>(&mut self, content: B) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content.into());
+    }
+}
+
+impl ResolverResult for RawGithubResolvedModule {
+    fn domain(&self) -> Domain {
+        Domain::Github
+    }
+
+    fn user(&self) -> &str {
+        &self.user
+    }
+
+    fn name(&self) -> &str {
+        &self.file_path
+    }
+
+    fn cache_identity(&self) -> String {
+        format!(
+            "github:{}/{}/{}/{}",
+            self.user, self.repo, self.git_ref, self.file_path
+        )
+    }
+
+    fn content(&self) -> Option<&[u8]> {
+        self.content.as_deref()
+    }
+
+    fn take_content(&mut self) -> Option<Vec<u8>> {
+        self.content.take()
+    }
+
+    fn set_content(&mut self, content: Vec<u8>) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+lazy_static! {
+    static ref RAW_GITHUB_ORIGIN: Origin = "https://raw.githubusercontent.com/"
+        .parse::<Url>()
+        .unwrap()
+        .origin();
+}
+
+/// Resolves modules directly from a `raw.githubusercontent.com` url, with no
+/// API round-trip to discover anything first — unlike
+/// [`super::repo::RepoLoader`], which resolves a `github.com/.../blob/<ref>/<path>`
+/// url by calling the commits API before it knows what to fetch.
+pub(super) struct RawGithubLoader;
+
+impl WebLoader for RawGithubLoader {
+    fn name(&self) -> &'static str {
+        "raw-github"
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        *origin == *RAW_GITHUB_ORIGIN
+    }
+
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>> {
+        Box::pin(async move {
+            let resolved = resolve_raw_github(url).await?;
+            Ok(Box::new(resolved) as Box<dyn ResolverResult + Send + Sync>)
+        })
+    }
+
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>> {
+        Box::pin(load_content(module))
+    }
+
+    fn fetch_signature<'a>(
+        &'a self,
+        module: &'a ResolvedModule,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let resolved = module.downcast_ref::<RawGithubResolvedModule>();
+            super::fetch_detached_signature(&resolved.build_raw_url()).await
+        })
+    }
+}
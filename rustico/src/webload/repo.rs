@@ -0,0 +1,540 @@
+use lazy_static::lazy_static;
+use url::{Origin, Url};
+
+use super::cache::{self, CachedBlob, CachedRef, RepoBlobKey, RepoRefKey};
+use super::credentials::{execute_with_retry, Credentials};
+use super::{
+    client, download_with_limit, is_full_sha, manifest, BoxFuture, Domain, InvalidUrl,
+    ResolvedModule, ResolverResult, WebError, WebLoader, MAX_MODULE_SIZE,
+};
+use crate::registry::FetchOutcome;
+use crate::service::Result;
+
+/// Which repository host a [`Repo`] url was parsed against, since GitHub and
+/// GitLab disagree on both url shape (`blob|raw` vs `-/blob|-/raw`) and
+/// commits-api shape (`sha` vs `id`, project path vs owner/repo segments).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Host {
+    GitHub,
+    GitLab,
+}
+
+impl Host {
+    fn from_origin(origin: &Origin) -> Option<Self> {
+        if *origin == *GITHUB_ORIGIN {
+            Some(Host::GitHub)
+        } else if *origin == *GITLAB_ORIGIN {
+            Some(Host::GitLab)
+        } else {
+            None
+        }
+    }
+
+    /// Cache-key discriminator, so `github.com/a/b` and `gitlab.com/a/b`
+    /// (unlikely, but possible) never share a cached ref or blob.
+    fn label(self) -> &'static str {
+        match self {
+            Host::GitHub => "github",
+            Host::GitLab => "gitlab",
+        }
+    }
+}
+
+/// The ref segment of a `blob`/`raw` url, modeled on cargo's `GitReference`
+/// (`Branch`/`Tag`/`Rev`). Unlike a cargo manifest's `branch =`/`tag =`/
+/// `rev =` keys, nothing in a `blob`/`raw` url says which kind a ref is, so a
+/// sha-shaped segment parses as `Rev` and anything else as `Branch`; both
+/// GitHub's and GitLab's commits api resolve all three the same way, so the
+/// distinction only matters for display, not for how resolution proceeds.
+#[derive(Debug, Clone)]
+enum GitReference {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
+
+impl GitReference {
+    fn parse(s: &str) -> Self {
+        if is_full_sha(s) {
+            GitReference::Rev(s.to_string())
+        } else {
+            GitReference::Branch(s.to_string())
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            GitReference::Branch(s) | GitReference::Tag(s) | GitReference::Rev(s) => s,
+        }
+    }
+}
+
+/// Parsed `github.com`/`gitlab.com` repository url.
+struct Repo<'a> {
+    host: Host,
+    user: &'a str,
+    repo: &'a str,
+    git_ref: Option<GitReference>,
+    file_path: Option<&'a str>,
+}
+
+impl<'a> Repo<'a> {
+    fn new(url: &'a Url, host: Host) -> Result<Self> {
+        Self::parse(url, host)
+    }
+
+    fn parse(url: &'a Url, host: Host) -> Result<Self> {
+        let trimmed = url.path().trim_matches('/');
+        let mut parts = trimmed.splitn(4, '/');
+        let user = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+        let repo = parts
+            .next()
+            .filter(|s| !s.is_empty())
+            .ok_or(InvalidUrl::InvalidPath)?;
+
+        // GitLab nests `blob`/`raw` one segment deeper, under `-`:
+        // `/<user>/<repo>/-/blob/<ref>/<path>`.
+        let kind_and_rest = match host {
+            Host::GitHub => (parts.next(), parts.next()),
+            Host::GitLab => match parts.next() {
+                None => (None, None),
+                Some("-") => {
+                    let rest = parts.next().unwrap_or_default();
+                    let mut rest = rest.splitn(2, '/');
+                    (rest.next(), rest.next())
+                }
+                Some(_) => return Err(InvalidUrl::InvalidPath.into()),
+            },
+        };
+
+        match kind_and_rest {
+            // /<user>/<repo>[/-]
+            (None, None) => Ok(Self {
+                host,
+                user,
+                repo,
+                git_ref: None,
+                file_path: None,
+            }),
+
+            // /<user>/<repo>/(blob|raw)/<ref>/<path>
+            (Some("blob" | "raw"), Some(rest)) => {
+                let mut rest = rest.splitn(2, '/');
+                let git_ref = rest
+                    .next()
+                    .filter(|s| !s.is_empty())
+                    .ok_or(InvalidUrl::InvalidPath)?;
+                let file_path = rest.next().filter(|s| !s.is_empty());
+                Ok(Self {
+                    host,
+                    user,
+                    repo,
+                    git_ref: Some(GitReference::parse(git_ref)),
+                    file_path,
+                })
+            }
+
+            _ => Err(InvalidUrl::InvalidPath.into()),
+        }
+    }
+
+    fn user(&self) -> &'a str {
+        self.user
+    }
+
+    fn repo(&self) -> &'a str {
+        self.repo
+    }
+
+    /// The ref as given in the url, defaulting to the repo's default branch.
+    fn git_ref(&self) -> &str {
+        self.git_ref.as_ref().map_or("HEAD", GitReference::as_str)
+    }
+
+    fn file_path(&self) -> Option<&'a str> {
+        self.file_path
+    }
+}
+
+/// Pick the one file in a repository-contents listing that's most likely to
+/// be the intended module: the only file, whatever its extension, if there's
+/// just one; otherwise a unique `.wasm`, or failing that a unique `.wat`.
+/// Unlike gists, the contents API doesn't report a file's language,
+/// so that third tier from [`super::gist`]'s heuristics doesn't apply here.
+fn guess_repo_file_name(entries: &[serde_json::Value]) -> Option<String> {
+    use itertools::Itertools;
+
+    let files: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.get("type").and_then(|t| t.as_str()) == Some("file"))
+        .filter_map(|entry| entry.get("name").and_then(|n| n.as_str()))
+        .collect();
+
+    if let [only] = files.as_slice() {
+        return Some(only.to_string());
+    }
+    if let Ok(name) = files.iter().filter(|name| name.ends_with(".wasm")).exactly_one() {
+        return Some((*name).to_string());
+    }
+    if let Ok(name) = files.iter().filter(|name| name.ends_with(".wat")).exactly_one() {
+        return Some((*name).to_string());
+    }
+    None
+}
+
+async fn guess_repo_file_path(
+    client: &reqwest::Client,
+    credentials: &Option<Credentials>,
+    host: Host,
+    user: &str,
+    repo: &str,
+    sha: &str,
+) -> Result<String> {
+    let entries: Vec<serde_json::Value> = match host {
+        Host::GitHub => {
+            let api_url = format!("https://api.github.com/repos/{user}/{repo}/contents?ref={sha}");
+            let response = execute_with_retry(client, || {
+                let request = client
+                    .request(reqwest::Method::GET, &api_url)
+                    .header("Accept", "application/vnd.github+json")
+                    .header("X-GitHub-Api-Version", "2022-11-28");
+                match credentials {
+                    Some(credentials) => credentials.apply(request),
+                    None => request,
+                }
+            })
+            .await?;
+            response.json().await.map_err(WebError::ReqwestError)?
+        }
+        Host::GitLab => {
+            let project = format!("{user}%2F{repo}");
+            let api_url =
+                format!("https://gitlab.com/api/v4/projects/{project}/repository/tree?ref={sha}");
+            let response = client
+                .get(&api_url)
+                .send()
+                .await
+                .map_err(WebError::TemporaryFailure)?
+                .error_for_status()
+                .map_err(WebError::TemporaryFailure)?;
+            let tree: Vec<serde_json::Value> =
+                response.json().await.map_err(WebError::ReqwestError)?;
+            // GitLab's tree api reports blobs as `"type": "blob"`, not
+            // `"file"`; normalize so `guess_repo_file_name` works unchanged.
+            tree.into_iter()
+                .map(|mut entry| {
+                    if entry.get("type").and_then(|t| t.as_str()) == Some("blob") {
+                        if let Some(value) = entry.get_mut("type") {
+                            *value = serde_json::Value::String("file".to_string());
+                        }
+                    }
+                    entry
+                })
+                .collect()
+        }
+    };
+    guess_repo_file_name(&entries).ok_or_else(|| WebError::NotWasm.into())
+}
+
+pub(super) async fn resolve_repo(url: &Url) -> Result<impl ResolverResult> {
+    debug_assert_eq!(url.scheme(), "https");
+    let host = Host::from_origin(&url.origin()).ok_or(InvalidUrl::RejectedOrigin)?;
+
+    let repo = Repo::new(url, host)?;
+    let user = repo.user().to_string();
+    let repo_name = repo.repo().to_string();
+    let git_ref = repo.git_ref().to_string();
+
+    let client = client()?;
+    let credentials = Credentials::resolve(host.label());
+    let key = RepoRefKey {
+        host: host.label(),
+        user: user.clone(),
+        repo: repo_name.clone(),
+        git_ref: git_ref.clone(),
+    };
+    let client_for_ref = client.clone();
+    let credentials_for_ref = credentials.clone();
+    let resolved_ref = cache::cached_repo_ref(key, move |previous| async move {
+        fetch_ref(
+            &client_for_ref,
+            &credentials_for_ref,
+            host,
+            &user,
+            &repo_name,
+            &git_ref,
+            previous,
+        )
+        .await
+    })
+    .await?;
+    let CachedRef { sha, .. } = resolved_ref;
+
+    let user = repo.user().to_string();
+    let repo_name = repo.repo().to_string();
+    let file_path = match repo.file_path() {
+        Some(path) => path.to_string(),
+        None => guess_repo_file_path(&client, &credentials, host, &user, &repo_name, &sha).await?,
+    };
+
+    Ok(RepoResolvedModule {
+        host,
+        user,
+        repo: repo_name,
+        sha,
+        file_path,
+        content: None,
+    })
+}
+
+/// Resolves `git_ref` (a branch, tag, or commit sha) to a concrete commit
+/// sha via `host`'s commits api, reusing `previous`'s `ETag` when present on
+/// GitHub; the GitLab request is never conditional.
+async fn fetch_ref(
+    client: &reqwest::Client,
+    credentials: &Option<Credentials>,
+    host: Host,
+    user: &str,
+    repo: &str,
+    git_ref: &str,
+    previous: Option<CachedRef>,
+) -> Result<FetchOutcome<CachedRef>> {
+    let etag = previous.as_ref().and_then(|r| r.etag.as_deref());
+    match host {
+        Host::GitHub => {
+            let api_url = format!("https://api.github.com/repos/{user}/{repo}/commits/{git_ref}");
+            let response = execute_with_retry(client, || {
+                let mut request = client
+                    .request(reqwest::Method::GET, &api_url)
+                    .header("Accept", "application/vnd.github+json")
+                    .header("X-GitHub-Api-Version", "2022-11-28");
+                if let Some(etag) = etag {
+                    request = request.header("If-None-Match", etag);
+                }
+                match credentials {
+                    Some(credentials) => credentials.apply(request),
+                    None => request,
+                }
+            })
+            .await?;
+
+            if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+                return Ok(FetchOutcome::Reuse);
+            }
+            let etag = response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let json = response
+                .json::<serde_json::Value>()
+                .await
+                .map_err(WebError::ReqwestError)?;
+            let sha = json
+                .get("sha")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or(WebError::UnexpectedResponse)?;
+            Ok(FetchOutcome::Replace(CachedRef { sha, etag }))
+        }
+        Host::GitLab => {
+            let project = format!("{user}%2F{repo}");
+            let api_url = format!(
+                "https://gitlab.com/api/v4/projects/{project}/repository/commits/{git_ref}"
+            );
+            let response = client
+                .get(&api_url)
+                .send()
+                .await
+                .map_err(WebError::TemporaryFailure)?
+                .error_for_status()
+                .map_err(WebError::TemporaryFailure)?;
+            let json = response
+                .json::<serde_json::Value>()
+                .await
+                .map_err(WebError::ReqwestError)?;
+            let sha = json
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or(WebError::UnexpectedResponse)?;
+            Ok(FetchOutcome::Replace(CachedRef { sha, etag: None }))
+        }
+    }
+}
+
+pub(crate) async fn load_content(module: &mut ResolvedModule) -> Result<()> {
+    if module.content().is_some() {
+        return Ok(());
+    }
+    let resolved = module.downcast::<RepoResolvedModule>();
+    let host = resolved.host;
+    let key = RepoBlobKey {
+        host: host.label(),
+        user: resolved.user.clone(),
+        repo: resolved.repo.clone(),
+        sha: resolved.sha.clone(),
+        file_path: resolved.file_path.clone(),
+    };
+    let fetch_url = resolved.build_raw_url();
+
+    let client = client()?;
+    let credentials = Credentials::resolve(host.label());
+    let cached = cache::cached_repo_blob(key, || async move {
+        let response = execute_with_retry(&client, || {
+            let request = client.request(reqwest::Method::GET, &fetch_url);
+            match &credentials {
+                Some(credentials) => credentials.apply(request),
+                None => request,
+            }
+        })
+        .await?;
+        let resolved_url = response.url().clone();
+        let content = download_with_limit(response, MAX_MODULE_SIZE).await?;
+        if !manifest::looks_like_wasm_module(&content) {
+            return Err(WebError::NotWasm.into());
+        }
+        Ok(CachedBlob { content, resolved_url })
+    })
+    .await?;
+    resolved.set_content(cached.content);
+    module.set_resolved_url(cached.resolved_url);
+    Ok(())
+}
+
+struct RepoResolvedModule {
+    host: Host,
+    user: String,
+    repo: String,
+    sha: String,
+    file_path: String,
+    content: Option<Vec<u8>>,
+}
+
+impl RepoResolvedModule {
+    fn build_raw_url(&self) -> String {
+        let Self {
+            host,
+            user,
+            repo,
+            sha,
+            file_path,
+            ..
+        } = self;
+        match host {
+            Host::GitHub => {
+                format!("https://raw.githubusercontent.com/{user}/{repo}/{sha}/{file_path}")
+            }
+            Host::GitLab => format!("https://gitlab.com/{user}/{repo}/-/raw/{sha}/{file_path}"),
+        }
+    }
+
+    fn set_content<B: Into<Vec<u8>>>(&mut self, content: B) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content.into());
+    }
+}
+
+impl ResolverResult for RepoResolvedModule {
+    fn domain(&self) -> Domain {
+        match self.host {
+            Host::GitHub => Domain::Github,
+            Host::GitLab => Domain::Other("gitlab"),
+        }
+    }
+
+    fn user(&self) -> &str {
+        &self.user
+    }
+
+    fn name(&self) -> &str {
+        &self.file_path
+    }
+
+    fn cache_identity(&self) -> String {
+        format!(
+            "{}:{}/{}/{}/{}",
+            self.host.label(),
+            self.user,
+            self.repo,
+            self.sha,
+            self.file_path
+        )
+    }
+
+    fn content(&self) -> Option<&[u8]> {
+        self.content.as_deref()
+    }
+
+    fn take_content(&mut self) -> Option<Vec<u8>> {
+        self.content.take()
+    }
+
+    fn set_content(&mut self, content: Vec<u8>) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+lazy_static! {
+    static ref GITHUB_ORIGIN: Origin = "https://github.com/".parse::<Url>().unwrap().origin();
+    static ref GITLAB_ORIGIN: Origin = "https://gitlab.com/".parse::<Url>().unwrap().origin();
+}
+
+/// Resolves modules from a normal GitHub or GitLab repository tree, as
+/// opposed to [`super::gist::GistLoader`]'s single-file gists:
+/// `/<user>/<repo>`, `/<user>/<repo>/blob/<ref>/<path>` and
+/// `/<user>/<repo>/raw/<ref>/<path>` on GitHub, or the same shapes nested
+/// under `/-/` on GitLab.
+pub(super) struct RepoLoader;
+
+impl WebLoader for RepoLoader {
+    fn name(&self) -> &'static str {
+        "repo"
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        Host::from_origin(origin).is_some()
+    }
+
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>> {
+        Box::pin(async move {
+            let resolved = resolve_repo(url).await?;
+            Ok(Box::new(resolved) as Box<dyn ResolverResult + Send + Sync>)
+        })
+    }
+
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>> {
+        Box::pin(load_content(module))
+    }
+
+    fn fetch_signature<'a>(
+        &'a self,
+        module: &'a ResolvedModule,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let resolved = module.downcast_ref::<RepoResolvedModule>();
+            super::fetch_detached_signature(&resolved.build_raw_url()).await
+        })
+    }
+}
@@ -0,0 +1,128 @@
+//! Ed25519 signature verification for web-loaded modules, filling in the
+//! "code authentication" possibility [`Domain`] has always left room for.
+//!
+//! Trust is scoped per `(Domain, user)`: a loader has no obligation to
+//! verify anything unless an operator has registered at least one trusted
+//! public key for that identity (see [`trust_key`]); once one is, a
+//! detached signature becomes mandatory and any mismatch or missing
+//! signature is rejected as [`WebError::UntrustedSignature`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use lazy_static::lazy_static;
+use url::Url;
+
+use super::{Domain, WebError};
+use crate::service::Result;
+
+/// A public key trusted to sign modules for some `(Domain, user)`, tagged
+/// with the id a signature footer references it by (so keys can rotate
+/// without invalidating signatures made under the old one).
+#[derive(Clone)]
+struct TrustedKey {
+    key_id: String,
+    key: VerifyingKey,
+}
+
+lazy_static! {
+    static ref KEYRING: Mutex<HashMap<(Domain, String), Vec<TrustedKey>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Registers `public_key` as trusted to sign modules published as `user` on
+/// `domain`, identified in signature footers by `key_id`. A domain/user pair
+/// may have more than one trusted key, e.g. while rotating to a new one.
+pub(crate) fn trust_key(
+    domain: Domain,
+    user: impl Into<String>,
+    key_id: impl Into<String>,
+    public_key: &[u8; 32],
+) -> Result<()> {
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| WebError::UntrustedSignature)?;
+    KEYRING
+        .lock()
+        .unwrap()
+        .entry((domain, user.into()))
+        .or_default()
+        .push(TrustedKey {
+            key_id: key_id.into(),
+            key,
+        });
+    Ok(())
+}
+
+/// Whether any key has been registered for `(domain, user)`. Loaders only
+/// need to fetch and check a signature when this is true — an unsigned
+/// module from an identity nobody has configured a key for is fine.
+pub(super) fn has_keys(domain: Domain, user: &str) -> bool {
+    KEYRING
+        .lock()
+        .unwrap()
+        .contains_key(&(domain, user.to_string()))
+}
+
+/// The exact bytes a signature must cover: the module's content followed by
+/// a footer binding the signing key id, the url the module was requested
+/// under (as given, not canonicalized) and its name, so a signature produced
+/// for one module can't be replayed against another even if their content
+/// happened to be identical. The same module requested under a
+/// differently-spelled url gets a different footer, and its signature does
+/// not verify there.
+fn signed_message(key_id: &str, url: &Url, name: &str, content: &[u8]) -> Vec<u8> {
+    let footer = format!("\0wotto-module-signature\0key={key_id}\0url={url}\0name={name}");
+    let mut message = Vec::with_capacity(content.len() + footer.len());
+    message.extend_from_slice(content);
+    message.extend_from_slice(footer.as_bytes());
+    message
+}
+
+/// The wire format of a `.sig` artifact: one line naming the key id, one
+/// line with the hex-encoded signature.
+fn parse_signature_file(signature_file: &[u8]) -> Option<(&str, Vec<u8>)> {
+    let text = std::str::from_utf8(signature_file).ok()?;
+    let (key_id, signature_hex) = text.trim().split_once('\n')?;
+    let signature = decode_hex(signature_hex.trim())?;
+    Some((key_id, signature))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Verifies `signature_file` (a `.sig` artifact fetched alongside `content`)
+/// against the keys trusted for `(domain, user)`, returning the id of
+/// whichever key matched.
+pub(super) fn verify(
+    domain: Domain,
+    user: &str,
+    url: &Url,
+    name: &str,
+    content: &[u8],
+    signature_file: &[u8],
+) -> Result<String> {
+    let (key_id, signature) =
+        parse_signature_file(signature_file).ok_or(WebError::UntrustedSignature)?;
+    let signature =
+        Signature::from_slice(&signature).map_err(|_| WebError::UntrustedSignature)?;
+    let message = signed_message(key_id, url, name, content);
+
+    let keyring = KEYRING.lock().unwrap();
+    let trusted = keyring
+        .get(&(domain, user.to_string()))
+        .ok_or(WebError::UntrustedSignature)?;
+    trusted
+        .iter()
+        .find(|candidate| {
+            candidate.key_id == key_id && candidate.key.verify(&message, &signature).is_ok()
+        })
+        .map(|candidate| candidate.key_id.clone())
+        .ok_or_else(|| WebError::UntrustedSignature.into())
+}
@@ -0,0 +1,139 @@
+use url::{Origin, Url};
+
+use super::{
+    client, download_with_limit, manifest, BoxFuture, Domain, ResolvedModule, ResolverResult,
+    WebError, WebLoader, MAX_MODULE_SIZE,
+};
+use crate::service::Result;
+
+/// A module resolved by [`SingleFileLoader`]. There's nothing to parse out
+/// of the url beyond its last path segment (used as a display name) — the
+/// whole url, taken as-is, already names the one file this host serves.
+struct SingleFileResolvedModule {
+    label: &'static str,
+    name: String,
+    /// The url's full path, distinct from `name` (its last segment, used
+    /// only for display) — this is what actually identifies the file should
+    /// two different directories happen to share a last segment.
+    path: String,
+    content: Option<Vec<u8>>,
+}
+
+impl ResolverResult for SingleFileResolvedModule {
+    fn domain(&self) -> Domain {
+        Domain::Other(self.label)
+    }
+
+    fn user(&self) -> &str {
+        ""
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn cache_identity(&self) -> String {
+        format!("{}:{}", self.label, self.path)
+    }
+
+    fn content(&self) -> Option<&[u8]> {
+        self.content.as_deref()
+    }
+
+    fn take_content(&mut self) -> Option<Vec<u8>> {
+        self.content.take()
+    }
+
+    fn set_content(&mut self, content: Vec<u8>) {
+        assert!(
+            self.content.is_none(),
+            "set_content() requires that content is None"
+        );
+        self.content = Some(content);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+async fn load_content(module: &mut ResolvedModule) -> Result<()> {
+    if module.content().is_some() {
+        return Ok(());
+    }
+    let fetch_url = module.url().clone();
+    let client = client()?;
+    let response = client
+        .get(fetch_url)
+        .send()
+        .await
+        .map_err(WebError::ReqwestError)?;
+    let resolved_url = response.url().clone();
+    let content = download_with_limit(response, MAX_MODULE_SIZE).await?;
+    if !manifest::looks_like_wasm_module(&content) {
+        return Err(WebError::NotWasm.into());
+    }
+    let resolved = module.downcast::<SingleFileResolvedModule>();
+    resolved.set_content(content);
+    module.set_resolved_url(resolved_url);
+    Ok(())
+}
+
+/// Resolves modules from a single operator-allowlisted host where the whole
+/// url already names one file — for a private mirror or internal artifact
+/// server that isn't worth a dedicated loader of its own. Construct with the
+/// host's [`Origin`] and a short label (used to tag resolved modules'
+/// [`Domain::Other`]) and hand it to [`super::register_loader`]; nothing
+/// here is registered by default, since there's no such host every
+/// deployment can be assumed to trust.
+pub(crate) struct SingleFileLoader {
+    origin: Origin,
+    label: &'static str,
+}
+
+impl SingleFileLoader {
+    #[allow(dead_code)]
+    pub(crate) fn new(origin: Origin, label: &'static str) -> Self {
+        Self { origin, label }
+    }
+}
+
+impl WebLoader for SingleFileLoader {
+    fn name(&self) -> &'static str {
+        "single-file"
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        *origin == self.origin
+    }
+
+    fn load<'a>(
+        &'a self,
+        url: &'a Url,
+    ) -> BoxFuture<'a, Result<Box<dyn ResolverResult + Send + Sync>>> {
+        let label = self.label;
+        Box::pin(async move {
+            let name = url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("module")
+                .to_string();
+            let path = url.path().to_string();
+            Ok(Box::new(SingleFileResolvedModule {
+                label,
+                name,
+                path,
+                content: None,
+            }) as Box<dyn ResolverResult + Send + Sync>)
+        })
+    }
+
+    fn load_content<'a>(&'a self, module: &'a mut ResolvedModule) -> BoxFuture<'a, Result<()>> {
+        Box::pin(load_content(module))
+    }
+}
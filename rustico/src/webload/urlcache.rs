@@ -0,0 +1,102 @@
+//! A content cache fronting `resolve()`/`ensure_content`, keyed by each
+//! [`super::ResolverResult::cache_identity`] — a loader-resolved identity
+//! (a gist's blob sha, a repo's resolved commit) rather than the raw
+//! request url — so differently-spelled links to the same content
+//! (`gist.github.com` vs `gist.githubusercontent.com`, a repo's `blob` vs
+//! `raw` form, a trailing `#file-...` fragment) collapse to one cached
+//! entry instead of each getting its own. Complements, rather than
+//! replaces, the per-loader caches in [`super::cache`], which key on the
+//! same kind of identifier but store an intermediate (a metadata response,
+//! a resolved ref) rather than final content.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+/// How long a cached entry may be served before it's treated as a miss.
+const ENTRY_TTL: Duration = Duration::from_secs(15 * 60);
+/// Cap on the number of entries kept in memory; the oldest one is evicted to
+/// make room for a new one once this is reached.
+const MAX_ENTRIES: usize = 512;
+
+/// A short, stable identifier derived from a module's
+/// [`super::ResolverResult::cache_identity`], suitable for invalidating or
+/// persisting its cached content. See [`cache_key`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey(String);
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+struct Entry {
+    content: Vec<u8>,
+    inserted_at: Instant,
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<CacheKey, Entry>> = Mutex::new(HashMap::new());
+}
+
+/// Computes `identity`'s [`CacheKey`]: the hex of a 64-bit SipHash, two
+/// nibbles per byte in little-endian order, so two urls that resolve to the
+/// same `cache_identity()` collapse to one entry regardless of how either
+/// was spelled.
+pub(crate) fn cache_key(identity: &str) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    identity.hash(&mut hasher);
+    let mut hex = String::with_capacity(16);
+    for byte in hasher.finish().to_le_bytes() {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    CacheKey(hex)
+}
+
+/// Content previously stored for `key`, if any and not yet past [`ENTRY_TTL`].
+pub(crate) fn get(key: &CacheKey) -> Option<Vec<u8>> {
+    let mut cache = CACHE.lock().unwrap();
+    match cache.get(key) {
+        Some(entry) if entry.inserted_at.elapsed() < ENTRY_TTL => Some(entry.content.clone()),
+        Some(_) => {
+            cache.remove(key);
+            None
+        }
+        None => None,
+    }
+}
+
+/// Stores `content` for `key`, evicting the single oldest entry first if the
+/// cache is already at [`MAX_ENTRIES`].
+pub(crate) fn insert(key: CacheKey, content: Vec<u8>) {
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= MAX_ENTRIES && !cache.contains_key(&key) {
+        if let Some(oldest) = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.inserted_at)
+            .map(|(key, _)| key.clone())
+        {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(
+        key,
+        Entry {
+            content,
+            inserted_at: Instant::now(),
+        },
+    );
+}
+
+/// Removes any cached content for `key`, e.g. once a caller determines it's
+/// stale (a failed integrity check, an explicit reload request).
+#[allow(dead_code)]
+pub(crate) fn invalidate(key: &CacheKey) {
+    CACHE.lock().unwrap().remove(key);
+}
@@ -0,0 +1,243 @@
+//! Persistent role-based access control, backed by a small SQLite database.
+//! Replaces the old `TrustedUsers` in-memory list (an exact-match `Vec` that
+//! forgot every grant on restart and could only express "fully trusted or
+//! not") with three tables: `users` (masks), `roles`, and `grants` (which
+//! management commands and module namespaces a role allows). A user can
+//! therefore be granted `load` and the `foo.*` namespace without being able
+//! to `quit`.
+//!
+//! Masks are matched with SQLite's own `GLOB` operator rather than string
+//! equality, so a grant can be scoped to `*!*@*.example.org` instead of one
+//! exact nick!user@host triple. The same operator is reused for namespace
+//! patterns like `foo.*`.
+
+use irc::client::prelude::Config;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use tracing::error;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS users (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        mask TEXT NOT NULL UNIQUE
+    )",
+    "CREATE TABLE IF NOT EXISTS roles (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE
+    )",
+    "CREATE TABLE IF NOT EXISTS user_roles (
+        user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+        PRIMARY KEY (user_id, role_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS grants (
+        role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+        kind TEXT NOT NULL CHECK (kind IN ('command', 'namespace')),
+        pattern TEXT NOT NULL,
+        PRIMARY KEY (role_id, kind, pattern)
+    )",
+];
+
+/// A role pre-granted to every mask (`*!*@*`) so module commands keep
+/// working out of the box; an operator who wants to lock namespaces down
+/// can simply revoke it.
+const PUBLIC_ROLE: &str = "public";
+/// The role `trust`/`default_trust` grant: every management command, every
+/// namespace.
+const TRUSTED_ROLE: &str = "trusted";
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum AclError {
+    #[error("acl database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("no such role: {0}")]
+    NoSuchRole(String),
+}
+
+/// One thing a role can allow: running a given management command, or
+/// loading/running modules from a given namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Capability<'a> {
+    Command(&'a str),
+    Namespace(&'a str),
+}
+
+impl Capability<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            Capability::Command(_) => "command",
+            Capability::Namespace(_) => "namespace",
+        }
+    }
+
+    fn pattern_target(&self) -> &str {
+        match self {
+            Capability::Command(name) | Capability::Namespace(name) => name,
+        }
+    }
+}
+
+pub(crate) struct Acl {
+    pool: SqlitePool,
+}
+
+impl Acl {
+    /// Opens (creating if necessary) the ACL database at `path`, then seeds
+    /// it with the [`PUBLIC_ROLE`]/[`TRUSTED_ROLE`] roles and, on first run,
+    /// grants `config`'s `default_trust` mask the trusted role.
+    pub(crate) async fn connect(path: &str, config: &Config) -> Result<Self, AclError> {
+        let url = format!("sqlite://{path}?mode=rwc");
+        let pool = SqlitePoolOptions::new().max_connections(4).connect(&url).await?;
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        let acl = Self { pool };
+        // only a brand-new database gets the default mask grants, so a later
+        // `revoke` of one of them survives a restart
+        let existing_roles: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM roles")
+            .fetch_one(&acl.pool)
+            .await?;
+        acl.seed_default_roles().await?;
+        if existing_roles == 0 {
+            acl.seed_default_trust(config).await?;
+        }
+        Ok(acl)
+    }
+
+    async fn seed_default_roles(&self) -> Result<(), AclError> {
+        for role in [TRUSTED_ROLE, PUBLIC_ROLE] {
+            sqlx::query("INSERT OR IGNORE INTO roles (name) VALUES (?1)")
+                .bind(role)
+                .execute(&self.pool)
+                .await?;
+        }
+        self.add_role_grant(TRUSTED_ROLE, "command", "*").await?;
+        self.add_role_grant(TRUSTED_ROLE, "namespace", "*").await?;
+        self.add_role_grant(PUBLIC_ROLE, "namespace", "*").await?;
+        // `ping` and `help` aren't sensitive and used to be reachable by
+        // anyone before commands gained capability checks; keep it that way.
+        self.add_role_grant(PUBLIC_ROLE, "command", "ping").await?;
+        self.add_role_grant(PUBLIC_ROLE, "command", "help").await?;
+        Ok(())
+    }
+
+    async fn add_role_grant(&self, role: &str, kind: &str, pattern: &str) -> Result<(), AclError> {
+        let role_id: i64 = sqlx::query_scalar("SELECT id FROM roles WHERE name = ?1")
+            .bind(role)
+            .fetch_one(&self.pool)
+            .await?;
+        sqlx::query("INSERT OR IGNORE INTO grants (role_id, kind, pattern) VALUES (?1, ?2, ?3)")
+            .bind(role_id)
+            .bind(kind)
+            .bind(pattern)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn seed_default_trust(&self, config: &Config) -> Result<(), AclError> {
+        self.grant("*!*@*", PUBLIC_ROLE).await?;
+        match config.get_option("default_trust") {
+            Some(mask) => {
+                self.grant(mask, TRUSTED_ROLE).await?;
+            }
+            None => error!("warning: no default_trust option specified"),
+        }
+        Ok(())
+    }
+
+    /// Grants `role` to `mask`, registering `mask` if it hasn't been seen
+    /// before. `mask` may be a glob pattern (`*!*@*.example.org`), not just
+    /// an exact `nick!user@host`. Returns whether the grant is new.
+    pub(crate) async fn grant(&self, mask: &str, role: &str) -> Result<bool, AclError> {
+        let mut tx = self.pool.begin().await?;
+        let role_id: i64 = sqlx::query_scalar("SELECT id FROM roles WHERE name = ?1")
+            .bind(role)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AclError::NoSuchRole(role.to_string()))?;
+        sqlx::query("INSERT OR IGNORE INTO users (mask) VALUES (?1)")
+            .bind(mask)
+            .execute(&mut *tx)
+            .await?;
+        let user_id: i64 = sqlx::query_scalar("SELECT id FROM users WHERE mask = ?1")
+            .bind(mask)
+            .fetch_one(&mut *tx)
+            .await?;
+        let result = sqlx::query("INSERT OR IGNORE INTO user_roles (user_id, role_id) VALUES (?1, ?2)")
+            .bind(user_id)
+            .bind(role_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes `role` from `mask`, if it was granted. A no-op (not an
+    /// error) if either doesn't exist.
+    pub(crate) async fn revoke(&self, mask: &str, role: &str) -> Result<(), AclError> {
+        sqlx::query(
+            "DELETE FROM user_roles \
+             WHERE user_id = (SELECT id FROM users WHERE mask = ?1) \
+               AND role_id = (SELECT id FROM roles WHERE name = ?2)",
+        )
+        .bind(mask)
+        .bind(role)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// All `(mask, role)` grants currently on file, for `trust-list`.
+    pub(crate) async fn list_grants(&self) -> Result<Vec<(String, String)>, AclError> {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT users.mask, roles.name FROM users \
+             JOIN user_roles ON user_roles.user_id = users.id \
+             JOIN roles ON roles.id = user_roles.role_id \
+             ORDER BY users.mask, roles.name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Whether `mask` holds the built-in [`TRUSTED_ROLE`], for callers
+    /// (like flood control) that want a wider budget for trusted users
+    /// without modeling it as a capability of its own.
+    pub(crate) async fn is_trusted(&self, mask: &str) -> Result<bool, AclError> {
+        let row = sqlx::query(
+            "SELECT 1 FROM users \
+             JOIN user_roles ON user_roles.user_id = users.id \
+             JOIN roles ON roles.id = user_roles.role_id \
+             WHERE ?1 GLOB users.mask AND roles.name = ?2 \
+             LIMIT 1",
+        )
+        .bind(mask)
+        .bind(TRUSTED_ROLE)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+
+    /// Whether the live mask `mask` (a `nick!user@host` triple) holds a
+    /// role granting `capability`.
+    pub(crate) async fn check_capability(
+        &self,
+        mask: &str,
+        capability: Capability<'_>,
+    ) -> Result<bool, AclError> {
+        let row = sqlx::query(
+            "SELECT 1 FROM users \
+             JOIN user_roles ON user_roles.user_id = users.id \
+             JOIN grants ON grants.role_id = user_roles.role_id \
+             WHERE ?1 GLOB users.mask AND grants.kind = ?2 AND ?3 GLOB grants.pattern \
+             LIMIT 1",
+        )
+        .bind(mask)
+        .bind(capability.kind())
+        .bind(capability.pattern_target())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+}
@@ -0,0 +1,73 @@
+//! Exponential backoff with jitter between reconnect attempts, so
+//! `crate::bot::connection_task` retrying against a server that's
+//! throttling, K-lining, or riding out a netsplit doesn't hammer it on a
+//! tight loop.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Delay before the first retry, before any backoff or jitter.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// The doubled delay never grows past this, however many consecutive
+/// failures there have been; jitter is added on top, so an actual sleep can
+/// reach 1.5 times this.
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+/// A connection that stays up at least this long is healthy again; the
+/// next failure starts backing off from scratch rather than picking up
+/// where a much older streak of failures left off.
+const STABILITY_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Tracks one [`crate::connection::Connection`]'s consecutive reconnect
+/// failures and computes how long to wait before the next attempt.
+pub(crate) struct Backoff {
+    failures: AtomicU32,
+}
+
+impl Backoff {
+    pub(crate) fn new() -> Self {
+        Self {
+            failures: AtomicU32::new(0),
+        }
+    }
+
+    /// How many consecutive failures have been recorded since the last time
+    /// a connection stayed up past [`STABILITY_THRESHOLD`].
+    pub(crate) fn failures(&self) -> u32 {
+        self.failures.load(Ordering::SeqCst)
+    }
+
+    /// Records one more failed attempt and returns how long to sleep before
+    /// retrying: [`BASE_DELAY`] doubled once per previous failure, capped at
+    /// [`MAX_DELAY`], plus up to 50% jitter so several networks backing off
+    /// at once don't all retry in lockstep.
+    pub(crate) fn next_delay(&self) -> Duration {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst);
+        let mut delay = BASE_DELAY;
+        for _ in 0..failures {
+            if delay >= MAX_DELAY {
+                break;
+            }
+            delay = (delay * 2).min(MAX_DELAY);
+        }
+        let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+        delay.mul_f64(1.0 + jitter)
+    }
+
+    /// Records that a connection just stayed up for `uptime` before
+    /// dropping; resets the failure count if it lasted past
+    /// [`STABILITY_THRESHOLD`], so a connection that's been stable for a
+    /// while doesn't inherit a long-ago streak of failures.
+    pub(crate) fn record_uptime(&self, uptime: Duration) {
+        if uptime >= STABILITY_THRESHOLD {
+            self.failures.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,61 +3,135 @@ use std::sync::Arc;
 use futures::prelude::*;
 use irc::client::prelude::*;
 use rusto_utils::debug::debug_arc;
+use tracing::{error, info, trace, warn};
 use warp::Filter;
 
+use crate::acl::Capability;
+use crate::connection::Connection;
 use crate::parsing;
 
+/// How long [`bot_main`] waits, after the shutdown tripwire fires, for
+/// in-flight command tasks to finish sending their replies before giving up
+/// on them.
+const SHUTDOWN_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Where [`bot_main`] loads its bot-wide settings from, and what
+/// [`config_watcher_task`] polls for changes.
+const CONFIG_PATH: &str = "rusto.toml";
+
 pub async fn bot_main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::load("rusto.toml")?;
+    let networks_config = crate::network::load(CONFIG_PATH)?;
+    let control_socket_path = networks_config.bot.control_socket_path.clone();
+    let web_bind_config = WebBindConfig::from_options(&networks_config.bot);
 
     let rustico = rustico::Service::new();
 
     let join_handles = {
         let mut join_handles = vec![];
 
-        let state = Arc::new(BotState::new(config, rustico));
+        let state = Arc::new(BotState::new(networks_config, rustico).await?);
+        let shutdown = state.shutdown();
 
         let web_task = tokio::spawn({
             let state = Arc::downgrade(&state);
-            async { web_server(state).await }
+            let shutdown = shutdown.clone();
+            async { web_server(state, shutdown, web_bind_config).await }
+        });
+
+        let control_socket_task = control_socket_path.map(|path| {
+            tokio::spawn({
+                let state = Arc::downgrade(&state);
+                let shutdown = shutdown.clone();
+                crate::control_socket::serve(path, state, shutdown)
+            })
         });
 
         let epoch_timer = std::thread::spawn({
             let state = Arc::downgrade(&state);
-            move || loop {
-                std::thread::sleep(std::time::Duration::from_millis(50));
-                let Some(state) = state.upgrade() else { break; };
-                state.rustico().increment_epoch();
+            let shutdown = shutdown.clone();
+            move || {
+                while !shutdown.is_tripped() {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    let Some(state) = state.upgrade() else { break; };
+                    state.rustico().increment_epoch();
+                }
             }
         });
         join_handles.push(epoch_timer);
 
         let ctrl_c_task = tokio::spawn(ctrl_c_monitor(Arc::downgrade(&state)));
 
-        let _ = state.clone().irc_task().await;
-        eprintln!("irc_task quit");
+        let config_watcher_task_handle = tokio::spawn({
+            let state = Arc::downgrade(&state);
+            let shutdown = shutdown.clone();
+            config_watcher_task(CONFIG_PATH, state, shutdown)
+        });
+
+        // one reconnect loop per configured network, running concurrently;
+        // a network dropping (or quitting on its own) never stops the
+        // others.
+        let connection_tasks: Vec<_> = state
+            .connections()
+            .map(|connection| {
+                let connection = connection.clone();
+                let state = state.clone();
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    let id = connection.id().clone();
+                    if let Err(error) = connection_task(connection, state, shutdown).await {
+                        error!(%id, %error, "connection task terminated with error");
+                    }
+                })
+            })
+            .collect();
+        for task in connection_tasks {
+            let _ = task.await;
+        }
+        trace!("all network connections stopped");
 
         ctrl_c_task.abort();
+        config_watcher_task_handle.abort();
+        if let Some(task) = &control_socket_task {
+            task.abort();
+        }
+
+        state.tasks().close();
+        trace!(grace_period = ?SHUTDOWN_GRACE_PERIOD, "waiting for in-flight commands to finish");
+        let drained = async {
+            // every spawned command task, tracked regardless of whether it
+            // ever acquires an engine permit...
+            state.tasks().wait().await;
+            // ...then confirm the engine itself is idle: every permit
+            // released means every `run_module` call actually returned,
+            // not just that its task wrapper did.
+            state.drain_engine().await;
+        };
+        if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, drained).await.is_err() {
+            warn!("grace period elapsed; some command tasks may not have replied");
+        }
 
-        // TODO close web task cleanly?
-        eprintln!("shutting down web server...");
-        let _ = tokio::time::timeout(std::time::Duration::from_millis(500), web_task).await;
+        trace!("shutting down web server");
+        let _ = web_task.await;
+        if let Some(task) = control_socket_task {
+            let _ = task.await;
+        }
 
         // state must have zero strong references at this point
         #[cfg(debug_assertions)]
         {
-            eprintln!("irc state: {}", debug_arc(&state));
+            use tracing::debug;
+            debug!("irc state: {}", debug_arc(&state));
         }
 
         join_handles
     };
 
-    eprintln!("shutting down epoch timer...");
+    trace!("shutting down epoch timer");
     for handle in join_handles {
         let _ = handle.join();
     }
 
-    eprintln!("all done, bye!");
+    trace!("all done, bye!");
 
     Ok(())
 }
@@ -65,29 +139,156 @@ pub async fn bot_main() -> Result<(), Box<dyn std::error::Error>> {
 async fn ctrl_c_monitor(state: std::sync::Weak<BotState>) {
     let Ok(_) = tokio::signal::ctrl_c().await else { return; };
     if let Some(state) = state.upgrade() {
-        eprintln!("received Ctrl-C; requesting quit");
-        state.request_quit();
+        info!("received Ctrl-C; requesting shutdown");
+        state.request_shutdown();
+    }
+}
+
+/// How often [`config_watcher_task`] checks `path`'s mtime. A plain poll
+/// loop, like the epoch timer above, rather than a filesystem-notify
+/// subscription — one infrequent check doesn't need its own dependency.
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Watches `path`'s `[bot]` section for changes and applies them to `state`
+/// via [`BotState::apply_bot_options`] — control token and engine permit
+/// count take effect immediately, with no need to restart or reconnect any
+/// network. A reload that fails to parse is logged and otherwise ignored,
+/// leaving the previous settings in place.
+async fn config_watcher_task(path: &str, state: std::sync::Weak<BotState>, shutdown: crate::shutdown::Shutdown) {
+    let mut last_modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(CONFIG_POLL_INTERVAL) => {}
+            _ = shutdown.tripped() => return,
+        }
+        let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) else {
+            continue;
+        };
+        if last_modified == Some(modified) {
+            continue;
+        }
+        last_modified = Some(modified);
+
+        let Some(state) = state.upgrade() else { return; };
+        match crate::network::load_bot_options(path) {
+            Ok(options) => state.apply_bot_options(options).await,
+            Err(error) => warn!(path, %error, "failed to reload config, keeping previous settings"),
+        }
+    }
+}
+
+/// One network's reconnect loop: keep (re)connecting and running
+/// [`irc_stream_handler`] against `connection` until the bot-wide
+/// `bot_shutdown` tripwire fires or `connection` is told to quit on its
+/// own (e.g. via the `quit` command), whichever comes first. A connection
+/// attempt or stream that fails is retried, not fatal; `connection`'s
+/// [`Backoff`](crate::backoff::Backoff) sleeps a growing, jittered delay
+/// between attempts first, so a server that's throttling, K-lining, or
+/// riding out a netsplit isn't hammered with instant reconnects.
+async fn connection_task(
+    connection: Arc<Connection>,
+    state: Arc<BotState>,
+    bot_shutdown: crate::shutdown::Shutdown,
+) -> Result<(), irc::error::Error> {
+    while !bot_shutdown.is_tripped() && !connection.is_quitting() {
+        info!(id = %connection.id(), "starting new client");
+        let attempt_started = std::time::Instant::now();
+
+        let stream = match connect(&connection).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                error!(id = %connection.id(), %error, "failed to connect");
+                if !wait_before_reconnect(&connection, &bot_shutdown).await {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        tokio::select! {
+            result = irc_stream_handler(stream, connection.clone(), state.clone()) => {
+                match result {
+                    Ok(_) => {}
+                    Err(error) => {
+                        error!(id = %connection.id(), %error, "irc stream loop terminated with error");
+                    }
+                }
+            }
+            _ = bot_shutdown.tripped() => {
+                trace!(id = %connection.id(), "shutdown requested; leaving irc stream loop");
+                break;
+            }
+            _ = connection.quitting().tripped() => {
+                trace!(id = %connection.id(), "quit requested; leaving irc stream loop");
+                break;
+            }
+        }
+
+        connection.backoff().record_uptime(attempt_started.elapsed());
+        if !wait_before_reconnect(&connection, &bot_shutdown).await {
+            break;
+        }
     }
+    Ok(())
+}
+
+/// Connects and identifies a fresh `Client` for `connection`, installing it
+/// as the connection's current client on success.
+async fn connect(connection: &Connection) -> Result<crate::transport::InboundStream, irc::error::Error> {
+    let mut client = Client::from_config(connection.config().clone()).await?;
+    client.identify()?;
+    let stream: crate::transport::InboundStream = Box::pin(client.stream()?);
+    connection.set_client(Box::new(client)).await;
+    Ok(stream)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Sleeps `connection`'s next backoff delay before the next reconnect
+/// attempt, logging the delay and the failure count it was computed from.
+/// Interruptible by either tripwire; returns whether the caller should keep
+/// looping (`false` means a quit arrived mid-sleep).
+async fn wait_before_reconnect(connection: &Connection, bot_shutdown: &crate::shutdown::Shutdown) -> bool {
+    let delay = connection.backoff().next_delay();
+    info!(
+        id = %connection.id(),
+        ?delay,
+        failures = connection.backoff().failures(),
+        "reconnecting"
+    );
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        _ = bot_shutdown.tripped() => false,
+        _ = connection.quitting().tripped() => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct UserMask {
     nick: String,
     user: String,
     host: String,
 }
 
-impl TryFrom<irc::proto::Prefix> for UserMask {
+impl TryFrom<&irc::proto::Prefix> for UserMask {
     type Error = ();
 
-    fn try_from(value: irc::proto::Prefix) -> Result<Self, Self::Error> {
+    fn try_from(value: &irc::proto::Prefix) -> Result<Self, Self::Error> {
         match value {
             Prefix::ServerName(_) => Err(()),
-            Prefix::Nickname(nick, user, host) => Ok(Self { nick, user, host }),
+            Prefix::Nickname(nick, user, host) => Ok(Self {
+                nick: nick.clone(),
+                user: user.clone(),
+                host: host.clone(),
+            }),
         }
     }
 }
 
+impl UserMask {
+    pub(crate) fn nick(&self) -> &str {
+        &self.nick
+    }
+}
+
 impl std::str::FromStr for UserMask {
     type Err = ();
 
@@ -102,304 +303,372 @@ impl std::str::FromStr for UserMask {
     }
 }
 
-mod state {
-    use std::fmt::Debug;
-    use std::sync::atomic::AtomicBool;
-    use std::sync::Arc;
+impl std::fmt::Display for UserMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}!{}@{}", self.nick, self.user, self.host)
+    }
+}
 
-    use irc::client::prelude::Config;
-    use irc::client::Client;
-    use irc::proto::Prefix;
-    use tokio::sync::{AcquireError, RwLock, Semaphore};
+fn prefix_to_string(prefix: &Prefix) -> String {
+    match prefix {
+        Prefix::ServerName(name) => name.clone(),
+        Prefix::Nickname(nick, user, host) => format!("{nick}!{user}@{host}"),
+    }
+}
 
-    use super::{BotCommand, CommandName, UserMask};
-    use crate::throttling::Throttler;
+/// Either an authenticated services account (from IRCv3's `account` message
+/// tag) or a bare `nick!user@host` hostmask. A hostmask can be spoofed by
+/// anyone who can open a connection with matching ident/host; an account is
+/// only ever attached by the server once SASL or NickServ has vouched for
+/// it, so [`resolve`](Self::resolve) prefers it whenever the server sent
+/// one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Identity {
+    Account(String),
+    Mask(UserMask),
+}
 
-    struct TrustedUsers {
-        list: Vec<UserMask>,
+impl Identity {
+    /// The pattern matched against the `users.mask` column by
+    /// [`crate::acl::Acl`]. Accounts share that same GLOB-matched column as
+    /// hostmasks, under an `account:<name>` prefix no real hostmask can ever
+    /// collide with (a mask always contains `!` and `@`), so `trust
+    /// account:<name>` works with zero schema changes.
+    pub(crate) fn acl_key(&self) -> String {
+        match self {
+            Identity::Account(name) => format!("account:{name}"),
+            Identity::Mask(mask) => mask.to_string(),
+        }
     }
 
-    impl TrustedUsers {
-        fn from_config(config: &Config) -> Self {
-            let list = match config.get_option("default_trust") {
-                Some(prefix) => match prefix.parse() {
-                    Ok(prefix) => vec![prefix],
-                    Err(_) => {
-                        eprintln!("warning: default_trust cannot be parsed!");
-                        vec![]
-                    }
-                },
-                None => {
-                    eprintln!("warning: no default_trust option specified");
-                    vec![]
-                }
-            };
-            Self { list }
+    /// Builds the identity to check against the ACL for a message: `account`
+    /// wins if present, falling back to `prefix`'s hostmask otherwise. `"*"`
+    /// is IRCv3's sentinel for "server supports `account-tag` but this
+    /// sender isn't logged in" — treated the same as no tag at all.
+    pub(crate) fn resolve(prefix: Option<&Prefix>, account: Option<&str>) -> Option<Self> {
+        if let Some(account) = account.filter(|a| !a.is_empty() && *a != "*") {
+            return Some(Identity::Account(account.to_string()));
         }
+        UserMask::try_from(prefix?).ok().map(Identity::Mask)
     }
+}
 
-    impl TrustedUsers {
-        fn is_trusted(&self, mask: &UserMask) -> bool {
-            self.list.iter().any(|x| x == mask)
+impl std::fmt::Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Identity::Account(name) => write!(f, "account:{name}"),
+            Identity::Mask(mask) => write!(f, "{mask}"),
         }
+    }
+}
 
-        fn is_trusted_prefix(&self, prefix: Option<Prefix>) -> bool {
-            if let Some(prefix) = prefix {
-                if let Ok(other_mask) = prefix.try_into() {
-                    self.is_trusted(&other_mask)
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        }
+/// The value of the incoming message's IRCv3 `account` tag, if the network
+/// negotiated `account-tag` and sent one.
+fn account_tag(message: &irc::proto::Message) -> Option<&str> {
+    message
+        .tags
+        .as_ref()?
+        .iter()
+        .find(|tag| tag.0 == "account")?
+        .1
+        .as_deref()
+}
 
-        fn add_trust(&mut self, mask: &UserMask) -> bool {
-            if self.is_trusted(mask) {
-                false
-            } else {
-                self.list.push(mask.clone());
-                true
-            }
-        }
+mod state {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
-        fn iter(&self) -> impl Iterator<Item = &UserMask> {
-            self.list.iter()
-        }
-    }
+    use irc::proto::Prefix;
+    use tokio::sync::{AcquireError, RwLock, Semaphore};
+    use tokio_util::task::TaskTracker;
 
-    async fn check_trust(state: &BotState, prefix: Option<Prefix>) -> bool {
-        state.trusted.read().await.is_trusted_prefix(prefix)
-    }
+    use super::{BotCommand, CommandName};
+    use crate::commands::{Command, CommandRegistry, Reply};
+    use crate::connection::Connection;
+    use crate::network::{BotOptions, NetworkId};
+    use crate::shutdown::Shutdown;
+
+    /// `engine_permits`'s default when `rusto.toml`'s `[bot]` section
+    /// doesn't set one.
+    const DEFAULT_ENGINE_PERMITS: usize = 2;
 
     pub(crate) struct BotState {
-        config: Config,
-        client: RwLock<Option<Client>>,
+        connections: HashMap<NetworkId, Arc<Connection>>,
         rustico: rustico::Service,
-        trusted: RwLock<TrustedUsers>,
-        throttler: Throttler,
+        commands: CommandRegistry,
+        control_token: RwLock<Option<String>>,
         engine_semaphore: Semaphore,
-        quitting: AtomicBool,
+        /// The `engine_semaphore`'s configured total, tracked separately
+        /// since a `Semaphore` only exposes how many permits are currently
+        /// *available*, not how many it was built with — needed to compute
+        /// the delta when [`apply_bot_options`](Self::apply_bot_options)
+        /// observes a new `engine_permits`.
+        engine_capacity: AtomicUsize,
+        shutdown: Shutdown,
+        tasks: TaskTracker,
+        events: tokio::sync::broadcast::Sender<crate::control::Event>,
     }
 
     impl BotState {
-        pub(crate) fn new(config: Config, rustico: rustico::Service) -> Self {
-            let throttler = Throttler::make()
-                .layer(5, 2500)
-                .layer(2, 150)
-                .layer(1, 50)
-                .build();
-            let engine_semaphore = Semaphore::new(2);
-            let trusted = TrustedUsers::from_config(&config);
-            Self {
-                config,
-                client: RwLock::new(None),
+        pub(crate) async fn new(
+            networks_config: crate::network::NetworksConfig,
+            rustico: rustico::Service,
+        ) -> Result<Self, crate::acl::AclError> {
+            let engine_capacity = networks_config.bot.engine_permits.unwrap_or(DEFAULT_ENGINE_PERMITS);
+            let engine_semaphore = Semaphore::new(engine_capacity);
+            let mut connections = HashMap::new();
+            for (id, config) in networks_config.networks {
+                let connection = Connection::connect(id.clone(), config).await?;
+                connections.insert(id, Arc::new(connection));
+            }
+            let (events, _) = tokio::sync::broadcast::channel(64);
+            Ok(Self {
+                connections,
                 rustico,
-                trusted: RwLock::new(trusted),
-                throttler,
+                commands: CommandRegistry::with_defaults(),
+                control_token: RwLock::new(networks_config.bot.control_token),
                 engine_semaphore,
-                quitting: AtomicBool::new(false),
-            }
+                engine_capacity: AtomicUsize::new(engine_capacity),
+                shutdown: Shutdown::new(),
+                tasks: TaskTracker::new(),
+                events,
+            })
         }
 
-        pub(crate) fn client<F, T>(&self, f: F) -> Option<T>
-        where
-            F: FnOnce(&Client) -> T,
-        {
-            match self.client.try_read() {
-                Ok(guard) => guard.as_ref().map(f),
-                Err(_) => None,
-            }
+        pub(crate) fn commands(&self) -> &CommandRegistry {
+            &self.commands
+        }
+
+        pub(crate) fn shutdown(&self) -> Shutdown {
+            self.shutdown.clone()
+        }
+
+        pub(crate) fn tasks(&self) -> &TaskTracker {
+            &self.tasks
         }
 
         pub(crate) fn rustico(&self) -> &rustico::Service {
             &self.rustico
         }
 
-        pub(crate) async fn management_command(
-            slf: Arc<Self>,
-            source: Option<Prefix>,
-            response_target: String,
-            cmd: &BotCommand,
-        ) {
-            match cmd.command() {
-                CommandName::Plain(x) if x == "ping" => {
-                    slf.reply(response_target, "pong").await;
-                }
-                CommandName::Plain(x) if x == "join" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    let chans: Vec<_> = cmd.args.split_whitespace().collect();
-                    let _ = slf.client(|client| client.send_join(chans.join(",")));
-                }
-                CommandName::Plain(x) if x == "trust" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    if let Ok(mask) = cmd.args().trim().parse() {
-                        let mut trusted = slf.trusted.write().await;
-                        let message = if trusted.add_trust(&mask) {
-                            format!("I now trust {}", mask.nick)
-                        } else {
-                            format!("I already trust {}", mask.nick)
-                        };
-                        slf.reply(response_target, message).await;
-                    } else {
-                        eprintln!("invalid prefix: {:?}", cmd.args());
-                    }
-                }
-                CommandName::Plain(x) if x == "untrust" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    let mut trusted = slf.trusted.write().await;
-                    *trusted = TrustedUsers::from_config(&slf.config);
-                    eprintln!("trusted list reset");
-                }
-                CommandName::Plain(x) if x == "trust-list" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    let trusted = slf.trusted.read().await;
-                    eprintln!("Trusted list:");
-                    for p in trusted.iter() {
-                        eprintln!(" * {p:?}");
-                    }
-                }
-                CommandName::Plain(x) if x == "load" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    let module_name = cmd.args.trim().to_string();
-                    let state = slf.clone();
-                    tokio::spawn(async move {
-                        let response = match state.rustico().load_module(module_name).await {
-                            Ok(name) => format!("loaded module: {name}"),
-                            Err(error) => {
-                                eprintln!("management load failed: {error}");
-                                "cannot load module (check logs)".to_string()
-                            }
-                        };
-                        state.reply(response_target, response).await;
-                    });
-                }
-                CommandName::Plain(x) if x == "permits" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    let available_permits = slf.engine_semaphore.available_permits();
-                    slf.reply(
-                        response_target,
-                        format!("available permits: {available_permits}"),
-                    )
-                    .await;
-                }
-                CommandName::Plain(x) if x == "quit" => {
-                    if !check_trust(&slf, source).await {
-                        return;
-                    }
-                    slf.request_quit();
-                }
-                _ => {
-                    eprintln!("not a valid management command: {cmd:?}");
+        /// The control token an operator socket must present before it's
+        /// allowed to send anything but `auth`, if one is configured. Reads
+        /// through to whatever [`apply_bot_options`](Self::apply_bot_options)
+        /// last set, so a reload takes effect for the next socket without a
+        /// restart.
+        pub(crate) async fn control_token(&self) -> Option<String> {
+            self.control_token.read().await.clone()
+        }
+
+        /// Applies a freshly reloaded `[bot]` section: swaps the control
+        /// token and grows or shrinks the engine semaphore to match
+        /// `engine_permits`, without touching any network connection.
+        /// Called by the config-watcher task in [`super::bot_main`]; never
+        /// given a config that failed to parse, so there's nothing here to
+        /// roll back.
+        pub(crate) async fn apply_bot_options(&self, new: BotOptions) {
+            {
+                let mut control_token = self.control_token.write().await;
+                if *control_token != new.control_token {
+                    info!(
+                        updated = new.control_token.is_some(),
+                        "config reload: control token changed"
+                    );
+                    *control_token = new.control_token;
                 }
             }
+
+            let new_permits = new.engine_permits.unwrap_or(DEFAULT_ENGINE_PERMITS);
+            let old_permits = self.engine_capacity.swap(new_permits, Ordering::SeqCst);
+            if new_permits > old_permits {
+                self.engine_semaphore.add_permits(new_permits - old_permits);
+                info!(old_permits, new_permits, "config reload: engine permits changed");
+            } else if new_permits < old_permits {
+                self.engine_semaphore.forget_permits(old_permits - new_permits);
+                info!(old_permits, new_permits, "config reload: engine permits changed");
+            }
         }
 
-        #[tracing::instrument]
-        pub(crate) async fn reply<R: AsRef<str> + Debug, M: AsRef<str> + Debug>(
-            &self,
-            response_target: R,
-            message: M,
-        ) {
-            const MAX_SIZE: usize = 512;
-            let target = response_target.as_ref();
-            let message = message.as_ref();
-
-            for (i, line) in message
-                .split_terminator(|c| c == '\r' || c == '\n')
-                .filter(|x| !x.is_empty())
-                .enumerate()
-            {
-                let prefix = if i == 0 { "\x02>\x0f" } else { "\x02:\x0f" };
-                let line = format!("{prefix}{line}");
-                let overhead = target.bytes().len() + b"PRIVMSG   :\r\n".len();
-                let max_payload_size = MAX_SIZE.saturating_sub(overhead);
-                let boundary = line.floor_char_boundary(max_payload_size);
-                self.throttler.acquire_one().await;
-                let _ = self.client(|client| client.send_privmsg(target, &line[..boundary]));
+        /// Looks up a configured network by its `[[network]]` `id`.
+        pub(crate) fn connection(&self, id: &str) -> Option<Arc<Connection>> {
+            self.connections.get(id).cloned()
+        }
+
+        pub(crate) fn connections(&self) -> impl Iterator<Item = &Arc<Connection>> {
+            self.connections.values()
+        }
+
+        pub(crate) fn available_permits(&self) -> usize {
+            self.engine_semaphore.available_permits()
+        }
+
+        /// Resolves once every permit handed out by [`engine_permit`]
+        /// has been released, i.e. the engine has no module call still
+        /// running. Used during shutdown, alongside draining
+        /// [`Self::tasks`], to confirm there's really nothing left in
+        /// flight rather than trusting a fixed timeout.
+        pub(crate) async fn drain_engine(&self) {
+            let total = self.engine_capacity.load(Ordering::SeqCst) as u32;
+            if total == 0 {
+                return;
             }
+            let _ = self.engine_semaphore.acquire_many(total).await;
         }
 
-        pub(crate) async fn engine_permit(&self) -> Result<impl Drop + '_, AcquireError> {
-            self.engine_semaphore.acquire().await
+        pub(crate) fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<crate::control::Event> {
+            self.events.subscribe()
         }
 
-        pub(crate) async fn irc_task(self: Arc<Self>) -> Result<(), irc::error::Error> {
-            while !self.quitting.load(std::sync::atomic::Ordering::SeqCst) {
-                eprintln!("starting new client...");
-                let mut client = Client::from_config(self.config.clone()).await?;
-                client.identify()?;
-                let stream = client.stream()?;
-                *self.client.write().await = Some(client);
-                match super::irc_stream_handler(stream, self.clone()).await {
-                    Ok(_) => {}
-                    Err(error) => {
-                        eprintln!("irc stream loop terminated with error: {error}");
-                    }
-                }
+        /// Publishes to any control sockets subscribed via
+        /// [`subscribe_events`](Self::subscribe_events). A no-op if nobody
+        /// is listening.
+        pub(crate) fn publish_event(&self, event: crate::control::Event) {
+            let _ = self.events.send(event);
+        }
+
+        /// Looks `cmd` up in [`Self::commands`], checks its
+        /// [`Command::required_capability`](crate::commands::Command::required_capability)
+        /// against `source` on `connection`'s ACL, and runs it.
+        pub(crate) async fn dispatch_command(
+            slf: Arc<Self>,
+            connection: Arc<Connection>,
+            source: Option<Prefix>,
+            account: Option<String>,
+            response_target: String,
+            cmd: &BotCommand,
+        ) {
+            let CommandName::Plain(name) = cmd.command() else {
+                warn!(?cmd, "not a valid management command");
+                return;
+            };
+            let Some(command) = slf.commands.get(name) else {
+                warn!(?cmd, "not a valid management command");
+                return;
+            };
+            let ctx = crate::commands::CommandCtx {
+                state: slf.clone(),
+                connection: connection.clone(),
+                source,
+                account,
+                response_target: response_target.clone(),
+                args: cmd.args().to_string(),
+            };
+            if !ctx.check_capability(command.required_capability()).await {
+                return;
+            }
+            match command.run(ctx).await {
+                Reply::None => {}
+                Reply::Text(text) => connection.reply(response_target, text).await,
             }
-            Ok(())
         }
 
-        pub(crate) fn request_quit(&self) {
-            let already_quitting = self.quitting
-                .swap(true, std::sync::atomic::Ordering::SeqCst);
-            if !already_quitting {
-                let _ = self.client(|client| client.send_quit("requested"));
+        /// Stops the bot-wide epoch timer and web server, and requests that
+        /// every configured network quit too; unlike a single network's own
+        /// `quit` command, this is full-process shutdown (e.g. on Ctrl-C).
+        pub(crate) fn request_shutdown(&self) {
+            if !self.shutdown.is_tripped() {
+                self.shutdown.fire();
             }
+            for connection in self.connections.values() {
+                connection.request_quit();
+            }
+        }
+
+        pub(crate) async fn engine_permit(&self) -> Result<impl Drop + '_, AcquireError> {
+            self.engine_semaphore.acquire().await
         }
     }
 
     impl core::fmt::Debug for BotState {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.debug_struct("BotState").field("quitting", &self.quitting).finish()
-    }
+            f.debug_struct("BotState")
+                .field("networks", &self.connections.len())
+                .field("quitting", &self.shutdown.is_tripped())
+                .finish()
+        }
     }
 }
 
-use state::BotState;
+pub(crate) use state::BotState;
+
+/// Builds the [`crate::control::Event`] a subscribed `/control` (or Unix
+/// socket) client would want to see for `message`, if its command is one a
+/// dashboard cares about watching live (channel/private traffic, joins,
+/// parts, notices, nick changes). Anything else (PING/PONG, numerics, ...)
+/// has no corresponding event and is silently skipped.
+fn irc_event_for(network: &str, message: &irc::proto::Message) -> Option<crate::control::Event> {
+    let network = network.to_string();
+    let source = message.prefix.as_ref().map(prefix_to_string);
+    match &message.command {
+        Command::PRIVMSG(target, text) => Some(crate::control::Event::Privmsg {
+            network,
+            source,
+            target: target.clone(),
+            text: text.clone(),
+        }),
+        Command::NOTICE(target, text) => Some(crate::control::Event::Notice {
+            network,
+            source,
+            target: target.clone(),
+            text: text.clone(),
+        }),
+        Command::JOIN(channel, _, _) => Some(crate::control::Event::Join {
+            network,
+            source,
+            channel: channel.clone(),
+        }),
+        Command::PART(channel, reason) => Some(crate::control::Event::Part {
+            network,
+            source,
+            channel: channel.clone(),
+            reason: reason.clone(),
+        }),
+        Command::NICK(new_nick) => Some(crate::control::Event::NickChange {
+            network,
+            source,
+            new_nick: new_nick.clone(),
+        }),
+        _ => None,
+    }
+}
 
 async fn irc_stream_handler(
-    mut stream: irc::client::ClientStream,
+    mut stream: crate::transport::InboundStream,
+    connection: Arc<Connection>,
     state: Arc<BotState>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // let client = state.client();
     while let Some(message) = stream.next().await.transpose()? {
         println!("\x1b[2m{}\x1b[0m", message.to_string().trim_end());
-        // let prefixes = [
-        //     "!",
-        //     &format!("{} ", client.current_nickname()),
-        //     &format!("{}:", client.current_nickname()),
-        //     &format!("{}!", client.current_nickname()),
-        // ];
+        if let Some(event) = irc_event_for(connection.id().as_str(), &message) {
+            state.publish_event(event);
+        }
         #[allow(clippy::single_match)]
         match message.command {
             Command::PRIVMSG(_, ref text) => {
                 if let Ok(cmd) = BotCommand::parse(&[], text) {
-                    eprintln!("got cmd {cmd:?}");
+                    if !connection.check_flood(message.prefix.as_ref()).await {
+                        continue;
+                    }
+                    trace!(?cmd, "got command");
                     let Some(response_target) = message.response_target().map(str::to_owned) else { break; };
-                    let w = Arc::downgrade(&state);
+                    let account = account_tag(&message).map(str::to_owned);
+                    state.publish_event(crate::control::Event::Command {
+                        network: connection.id().to_string(),
+                        source: message.prefix.as_ref().map(prefix_to_string),
+                        command: cmd.command().to_string(),
+                    });
+                    let w = Arc::downgrade(&connection);
                     handle_command(
                         message.prefix,
+                        account,
                         response_target.clone(),
                         cmd,
+                        connection.clone(),
                         state.clone(),
                         move |response| async move {
-                            if let Some(state) = w.upgrade() {
-                                state.reply(response_target, response).await;
+                            if let Some(connection) = w.upgrade() {
+                                connection.reply(response_target, response).await;
                             }
                         },
                     );
@@ -414,8 +683,10 @@ async fn irc_stream_handler(
 
 fn handle_command<F, Fut>(
     source: Option<irc::proto::Prefix>,
+    account: Option<String>,
     response_target: String,
     cmd: BotCommand,
+    connection: Arc<Connection>,
     state: Arc<BotState>,
     handler: F,
 ) where
@@ -425,26 +696,41 @@ fn handle_command<F, Fut>(
     let args = cmd.args().to_string();
     let (module_name, entry_point) = match cmd.command() {
         CommandName::Plain(_) => {
-            tokio::spawn(async move {
-                BotState::management_command(state, source, response_target, &cmd).await;
-            });
+            let tasks = state.tasks().clone();
+            tokio::spawn(tasks.track_future(async move {
+                BotState::dispatch_command(state, connection, source, account, response_target, &cmd).await;
+            }));
             return;
         }
         CommandName::Namespaced(ns, name) => (ns.to_string(), name.to_string()),
     };
     let task_name = format!("command::{module_name}::{entry_point}");
     let run_task = tokio::task::Builder::new().name(&task_name);
-    run_task.spawn(async move {
+    let tasks = state.tasks().clone();
+    run_task.spawn(tasks.track_future(async move {
+        if !connection
+            .check_capability(source.as_ref(), account.as_deref(), Capability::Namespace(&module_name))
+            .await
+        {
+            return;
+        }
         let Ok(permit) = state.engine_permit().await else { return; };
         match state
             .rustico()
             .run_module(&module_name, &entry_point, &args)
             .await
         {
-            Ok(s) => handler(s).await,
+            Ok(s) => {
+                state.publish_event(crate::control::Event::ModuleOutput {
+                    network: connection.id().to_string(),
+                    module: format!("{module_name}.{entry_point}"),
+                    output: s.clone(),
+                });
+                handler(s).await;
+            }
             Err(rustico::Error::TimedOut) => {
                 // TODO irc code shouldn't be mixed here I think
-                state
+                connection
                     .reply(
                         response_target,
                         format!(
@@ -455,13 +741,13 @@ fn handle_command<F, Fut>(
                     .await;
             }
             Err(err) => {
-                eprintln!("error on command: {err}");
+                error!(%err, "error on command");
             }
         }
         // being super-explicit that engine permit is released only after the
         // whole response has been sent out:
         drop(permit);
-    }).unwrap();
+    })).unwrap();
 }
 
 struct ParseError;
@@ -502,42 +788,273 @@ impl BotCommand {
     }
 }
 
-async fn web_server(state: std::sync::Weak<BotState>) {
+/// Rejection produced by [`auth`] when a request's `Authorization` header is
+/// missing or doesn't match the configured `control_token`. Turned into a
+/// bare 401 by [`handle_auth_rejection`], so a guarded route's own handler
+/// never has to know this happened.
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Guards a mutating HTTP route behind the bot's `control_token`, the same
+/// setting that gates the `/control` websocket's `auth` request. With no
+/// token configured, every request passes — consistent with an unset
+/// `control_token` leaving `/control` open too; this is meant to be paired
+/// with binding to loopback, not left as the only access control. With one
+/// configured, the request must carry `Authorization: Bearer <token>`
+/// matching it, compared via [`constant_time_eq`] so a mismatch can't be
+/// timed to recover the token one byte at a time.
+fn auth(state: std::sync::Weak<BotState>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let state = state.clone();
+            async move {
+                let Some(state) = state.upgrade() else {
+                    return Err(warp::reject::custom(Unauthorized));
+                };
+                let Some(expected) = state.control_token().await else {
+                    return Ok(());
+                };
+                let provided = header.as_deref().and_then(|header| header.strip_prefix("Bearer "));
+                match provided {
+                    Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+                    _ => Err(warp::reject::custom(Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Whether `a` and `b` hold the same bytes, without the comparison
+/// short-circuiting on the first mismatching byte — only their lengths (not
+/// in general secret for a token) affect how much work this does. Shared
+/// with `control`'s websocket `auth` request, which guards the same
+/// `control_token`.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// `warp::Filter::recover` handler completing [`auth`]: an [`Unauthorized`]
+/// rejection becomes a 401; anything else (a route genuinely not found)
+/// passes through as warp's default.
+async fn handle_auth_rejection(
+    rejection: warp::Rejection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "unauthorized" })),
+            warp::http::StatusCode::UNAUTHORIZED,
+        ))
+    } else {
+        Err(rejection)
+    }
+}
+
+/// Where [`web_server`] listens, captured once at startup from `[bot]`'s
+/// `web_bind_host`/`web_bind_port`/`web_tls_cert_path`/`web_tls_key_path`
+/// settings — these aren't hot-reloaded by `config_watcher_task`, since
+/// changing them means rebinding the listener, not just swapping a value an
+/// in-flight request reads.
+#[derive(Debug, Clone)]
+pub(crate) struct WebBindConfig {
+    host: String,
+    port: u16,
+    /// `(cert_path, key_path)`, both required together to terminate TLS.
+    tls: Option<(String, String)>,
+}
+
+impl WebBindConfig {
+    fn from_options(options: &crate::network::BotOptions) -> Self {
+        Self {
+            host: options.web_bind_host.clone().unwrap_or_else(|| "127.0.0.1".to_string()),
+            port: options.web_bind_port.unwrap_or(3030),
+            tls: match (&options.web_tls_cert_path, &options.web_tls_key_path) {
+                (Some(cert), Some(key)) => Some((cert.clone(), key.clone())),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Rebuilds a channel name from the `/{type}/{name}` path segments every
+/// `/join`, `/part`, and `/topic` route uses: `type == "hash"` means `name`
+/// is missing its leading `#` (so it round-trips through a URL path
+/// segment without needing to percent-encode it), anything else is taken as
+/// a literal prefix (e.g. `+` or `&`) glued straight onto `name`; a word
+/// like `plus` is not translated.
+fn channel_name(chan_type: &str, chan_name: &str) -> String {
+    if chan_type == "hash" {
+        format!("#{chan_name}")
+    } else {
+        format!("{chan_type}{chan_name}")
+    }
+}
+
+/// Optional channel key/password for `POST /join/...`, passed as
+/// `?key=...` since a key can itself contain characters awkward to carry in
+/// a path segment.
+#[derive(Debug, serde::Deserialize, Default)]
+struct JoinQuery {
+    key: Option<String>,
+}
+
+async fn web_server(state: std::sync::Weak<BotState>, shutdown: crate::shutdown::Shutdown, bind: WebBindConfig) {
     // GET /hello/warp => 200 OK with body "Hello, warp!"
     let hello = warp::path!("hello" / String).map(|name| format!("Hello, {}!", name));
+
+    // the typed request/response control plane; see `crate::control`. The
+    // token is re-read per connection rather than captured once, so a
+    // config-watcher reload takes effect for the next socket without
+    // restarting the web server.
+    let control = warp::path("control").and(warp::ws()).map({
+        let state = state.clone();
+        move |ws: warp::ws::Ws| {
+            let state = state.clone();
+            ws.on_upgrade(move |socket| async move {
+                let control_token = match state.upgrade() {
+                    Some(state) => state.control_token().await,
+                    None => None,
+                };
+                crate::control::handle_socket(socket, state, control_token).await
+            })
+        }
+    });
     let load_module = warp::path!("load" / String)
         .and(warp::post())
+        .and(auth(state.clone()))
         .then({
             let state = state.clone();
             move |module: String| {
                 let state = state.clone();
                 async move {
                     let Some(state) = state.upgrade() else { return; };
-                    match state.rustico().load_module(module.clone()).await {
-                        Ok(_) => eprintln!("loaded module {module}"),
-                        Err(err) => eprintln!("cannot load module {module}: {err}"),
+                    match state.rustico().load_module(module.clone(), false).await {
+                        Ok(_) => info!(module, "loaded module"),
+                        Err(err) => error!(module, %err, "cannot load module"),
                     };
                 }
             }
         })
         .map(|_| "");
 
-    let join_channel = warp::path!("join" / String / String)
+    // POST /modules/upload: push a `.wasm` module over HTTP instead of
+    // requiring filesystem access on the host. See `handle_module_upload`.
+    let upload_module = warp::path!("modules" / "upload")
         .and(warp::post())
+        .and(auth(state.clone()))
+        .and(warp::multipart::form().max_length(MODULE_UPLOAD_MAX_BYTES))
+        .and_then({
+            let state = state.clone();
+            move |form: warp::multipart::FormData| handle_module_upload(state.clone(), form)
+        });
+
+    // POST /modules/reload: force an on-demand rescan of the module
+    // directory instead of waiting for the filesystem watcher's next
+    // event. See `rustico::Service::rescan_modules`.
+    let reload_modules = warp::path!("modules" / "reload")
+        .and(warp::post())
+        .and(auth(state.clone()))
         .then({
             let state = state.clone();
-            move |chan_type: String, chan_name: String| {
-                let chan_name = if chan_type == "hash" {
-                    format!("#{chan_name}")
-                } else {
-                    format!("{chan_type}{chan_name}")
-                };
+            move || {
+                let state = state.clone();
+                async move {
+                    let Some(state) = state.upgrade() else {
+                        return warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({ "error": "shutting down" })),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        );
+                    };
+                    let report = state.rustico().rescan_modules().await;
+                    warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "added": report.added,
+                            "reloaded": report.reloaded,
+                            "dropped": report.dropped,
+                        })),
+                        warp::http::StatusCode::OK,
+                    )
+                }
+            }
+        });
+
+    let join_channel = warp::path!("join" / String / String / String)
+        .and(warp::post())
+        .and(auth(state.clone()))
+        .and(warp::query::<JoinQuery>())
+        .then({
+            let state = state.clone();
+            move |network: String, chan_type: String, chan_name: String, query: JoinQuery| {
+                let chan_name = channel_name(&chan_type, &chan_name);
                 let state = state.clone();
                 async move {
                     let Some(state) = state.upgrade() else { return; };
-                    match state.client(|client| client.send_join(&chan_name)) {
+                    let Some(connection) = state.connection(&network) else {
+                        error!(channel = chan_name, network, "cannot join channel: no such network");
+                        return;
+                    };
+                    match connection.client(|client| client.send_join(&chan_name, query.key.as_deref())) {
+                        Some(Ok(_)) => info!(channel = chan_name, "joined channel"),
+                        Some(Err(err)) => error!(channel = chan_name, %err, "cannot join channel"),
+                        None => {}
+                    }
+                }
+            }
+        })
+        .map(|_| "");
+
+    let part_channel = warp::path!("part" / String / String / String)
+        .and(warp::post())
+        .and(auth(state.clone()))
+        .then({
+            let state = state.clone();
+            move |network: String, chan_type: String, chan_name: String| {
+                let chan_name = channel_name(&chan_type, &chan_name);
+                let state = state.clone();
+                async move {
+                    let Some(state) = state.upgrade() else { return; };
+                    let Some(connection) = state.connection(&network) else {
+                        error!(channel = chan_name, network, "cannot part channel: no such network");
+                        return;
+                    };
+                    match connection.client(|client| client.send_part(&chan_name, None)) {
+                        Some(Ok(_)) => info!(channel = chan_name, "parted channel"),
+                        Some(Err(err)) => error!(channel = chan_name, %err, "cannot part channel"),
+                        None => {}
+                    }
+                }
+            }
+        })
+        .map(|_| "");
+
+    // POST /say/{target}, /nick/{new}, and /topic/{type}/{name} below have no
+    // `{network}` path segment (unlike /join and /part): they act on
+    // whichever network comes first from `state.connections()`, which only
+    // does the right thing for a single-network bot. A multi-network
+    // deployment should prefer `control::RequestKind::Say`/`Quit`/etc. over
+    // the `/control` websocket, which always takes an explicit network.
+    let say = warp::path!("say" / String)
+        .and(warp::post())
+        .and(auth(state.clone()))
+        .and(warp::body::bytes())
+        .then({
+            let state = state.clone();
+            move |target: String, body: bytes::Bytes| {
+                let message = String::from_utf8_lossy(&body).into_owned();
+                let state = state.clone();
+                async move {
+                    let Some(state) = state.upgrade() else { return; };
+                    let Some(connection) = state.connections().next() else {
+                        error!(target, "cannot say: no configured network");
+                        return;
+                    };
+                    match connection.client(|client| client.send_privmsg(&target, &message)) {
                         Some(Ok(_)) => {}
-                        Some(Err(err)) => eprintln!("cannot join channel {chan_name}: {err}"),
+                        Some(Err(err)) => error!(target, %err, "cannot say"),
                         None => {}
                     }
                 }
@@ -545,7 +1062,294 @@ async fn web_server(state: std::sync::Weak<BotState>) {
         })
         .map(|_| "");
 
-    let filter: _ = hello.or(load_module).or(join_channel);
+    let nick = warp::path!("nick" / String)
+        .and(warp::post())
+        .and(auth(state.clone()))
+        .then({
+            let state = state.clone();
+            move |new_nick: String| {
+                let state = state.clone();
+                async move {
+                    let Some(state) = state.upgrade() else { return; };
+                    let Some(connection) = state.connections().next() else {
+                        error!(new_nick, "cannot change nick: no configured network");
+                        return;
+                    };
+                    match connection.client(|client| client.send_nick(&new_nick)) {
+                        Some(Ok(_)) => info!(new_nick, "changed nick"),
+                        Some(Err(err)) => error!(new_nick, %err, "cannot change nick"),
+                        None => {}
+                    }
+                }
+            }
+        })
+        .map(|_| "");
+
+    // POST /topic/{type}/{name}, the topic in the body.
+    let topic = warp::path!("topic" / String / String)
+        .and(warp::post())
+        .and(auth(state.clone()))
+        .and(warp::body::bytes())
+        .then({
+            let state = state.clone();
+            move |chan_type: String, chan_name: String, body: bytes::Bytes| {
+                let chan_name = channel_name(&chan_type, &chan_name);
+                let new_topic = String::from_utf8_lossy(&body).into_owned();
+                let state = state.clone();
+                async move {
+                    let Some(state) = state.upgrade() else { return; };
+                    let Some(connection) = state.connections().next() else {
+                        error!(channel = chan_name, "cannot set topic: no configured network");
+                        return;
+                    };
+                    match connection.client(|client| client.send_topic(&chan_name, &new_topic)) {
+                        Some(Ok(_)) => info!(channel = chan_name, "set topic"),
+                        Some(Err(err)) => error!(channel = chan_name, %err, "cannot set topic"),
+                        None => {}
+                    }
+                }
+            }
+        })
+        .map(|_| "");
+
+    let filter = hello
+        .or(load_module)
+        .or(upload_module)
+        .or(reload_modules)
+        .or(join_channel)
+        .or(part_channel)
+        .or(say)
+        .or(nick)
+        .or(topic)
+        .or(control)
+        .recover(handle_auth_rejection);
+
+    let ip: std::net::IpAddr = bind.host.parse().unwrap_or_else(|error| {
+        warn!(host = bind.host, %error, "web_server: invalid bind host, falling back to 127.0.0.1");
+        std::net::IpAddr::from([127, 0, 0, 1])
+    });
+    let addr = (ip, bind.port);
+    let shutdown_signal = async move { shutdown.tripped().await };
+
+    if let Some((cert_path, key_path)) = bind.tls {
+        let (_, serve) = warp::serve(filter)
+            .tls()
+            .cert_path(cert_path)
+            .key_path(key_path)
+            .bind_with_graceful_shutdown(addr, shutdown_signal);
+        serve.await;
+    } else {
+        let (_, serve) = warp::serve(filter).bind_with_graceful_shutdown(addr, shutdown_signal);
+        serve.await;
+    }
+}
+
+/// Upper bound on a `POST /modules/upload` body: the multipart stream is
+/// buffered fully in memory before the module ever reaches wasmtime, so this
+/// also bounds that buffer.
+const MODULE_UPLOAD_MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+/// The optional JSON `metadata` part of a `POST /modules/upload` request,
+/// alongside the required `module` part carrying the `.wasm` bytes
+/// themselves. Missing entirely (or missing individual fields) defaults to
+/// storing the upload addressable only by its content hash, without
+/// registering it under a name.
+#[derive(Debug, serde::Deserialize)]
+#[serde(default)]
+struct ModuleUploadMetadata {
+    name: Option<String>,
+    auto_load: bool,
+}
+
+impl Default for ModuleUploadMetadata {
+    fn default() -> Self {
+        Self {
+            name: None,
+            auto_load: true,
+        }
+    }
+}
+
+/// Handles `POST /modules/upload`: reads the `module` part (the raw `.wasm`
+/// bytes) and the optional `metadata` part (a [`ModuleUploadMetadata`]),
+/// then hands them to [`rustico::Service::upload_module`], which validates
+/// and stores them content-addressed and optionally registers them under a
+/// name right away. Responds with the stored module's content hash as
+/// `{"id": ...}` on success, or a 4xx/5xx with `{"error": ...}` otherwise.
+async fn handle_module_upload(
+    state: std::sync::Weak<BotState>,
+    form: warp::multipart::FormData,
+) -> Result<impl warp::Reply, std::convert::Infallible> {
+    use bytes::Buf;
+
+    let bad_request = |message: String| {
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": message })),
+            warp::http::StatusCode::BAD_REQUEST,
+        ))
+    };
+
+    let parts = match form.try_collect::<Vec<_>>().await {
+        Ok(parts) => parts,
+        Err(error) => return bad_request(format!("invalid multipart body: {error}")),
+    };
 
-    warp::serve(filter).run(([127, 0, 0, 1], 3030)).await;
+    let mut module_bytes = None;
+    let mut metadata = ModuleUploadMetadata::default();
+
+    for mut part in parts {
+        let mut bytes = Vec::new();
+        while let Some(Ok(chunk)) = part.data().await {
+            bytes.extend_from_slice(chunk.chunk());
+        }
+        match part.name() {
+            "module" => module_bytes = Some(bytes),
+            "metadata" => match serde_json::from_slice(&bytes) {
+                Ok(parsed) => metadata = parsed,
+                Err(error) => return bad_request(format!("invalid metadata: {error}")),
+            },
+            _ => {}
+        }
+    }
+
+    let Some(module_bytes) = module_bytes else {
+        return bad_request("missing `module` part".to_string());
+    };
+
+    let Some(state) = state.upgrade() else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "shutting down" })),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ));
+    };
+
+    match state
+        .rustico()
+        .upload_module(module_bytes, metadata.name, metadata.auto_load)
+        .await
+    {
+        Ok(id) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "id": id })),
+            warp::http::StatusCode::OK,
+        )),
+        Err(error) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": error.to_string() })),
+            warp::http::StatusCode::BAD_REQUEST,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use irc::client::prelude::Config;
+    use irc::proto::{Command as IrcCommand, Message, Prefix};
+
+    use super::*;
+    use crate::network::{BotOptions, NetworkId, NetworksConfig};
+    use crate::transport::{mock_inbound, MockTransport, SentMessage};
+
+    /// A bare-bones client config whose `default_trust` pre-trusts
+    /// `default_trust`, so tests don't need to go through the `trust`
+    /// command just to exercise a capability-gated one.
+    fn test_config(default_trust: &str) -> Config {
+        Config {
+            nickname: Some("wotto".to_string()),
+            options: Some(HashMap::from([(
+                "default_trust".to_string(),
+                default_trust.to_string(),
+            )])),
+            ..Config::default()
+        }
+    }
+
+    /// A `Connection` backed by its own scratch ACL database (so tests
+    /// don't trip over each other's grants) and no IRC client yet; callers
+    /// `set_client` a `MockTransport` before exercising it.
+    async fn test_connection(name: &str, default_trust: &str) -> Arc<Connection> {
+        let db_path = std::path::PathBuf::from(format!("wotto-acl-{name}.db"));
+        let _ = std::fs::remove_file(&db_path);
+        let connection = Connection::connect(NetworkId::new(name), test_config(default_trust))
+            .await
+            .expect("test acl database should open");
+        Arc::new(connection)
+    }
+
+    fn privmsg(nick: &str, target: &str, text: &str) -> Message {
+        Message {
+            tags: None,
+            prefix: Some(Prefix::Nickname(nick.to_string(), nick.to_string(), "host".to_string())),
+            command: IrcCommand::PRIVMSG(target.to_string(), text.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn full_command_flow_trusts_and_replies() {
+        let connection = test_connection("command-flow", "alice!alice@host").await;
+        let mock = Arc::new(MockTransport::new());
+        connection.set_client(Box::new(mock.clone())).await;
+
+        let state = Arc::new(
+            BotState::new(
+                NetworksConfig {
+                    bot: BotOptions::default(),
+                    networks: vec![],
+                },
+                rustico::Service::new(),
+            )
+            .await
+            .expect("state with zero networks should always build"),
+        );
+
+        let inbound = mock_inbound(vec![
+            privmsg("alice", "#chan", "!ping"),
+            privmsg("alice", "#chan", "!trust bob!bob@host2"),
+        ]);
+        irc_stream_handler(inbound, connection.clone(), state.clone())
+            .await
+            .expect("a scripted stream never errors");
+
+        // plain commands are dispatched onto a tracked, but not awaited,
+        // task; wait for them to actually finish before asserting.
+        state.tasks().close();
+        state.tasks().wait().await;
+
+        assert_eq!(
+            mock.sent(),
+            vec![
+                SentMessage::Privmsg {
+                    target: "#chan".to_string(),
+                    message: "\x02>\x0fpong".to_string(),
+                },
+                SentMessage::Privmsg {
+                    target: "#chan".to_string(),
+                    message: "\x02>\x0fI now trust bob".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_splits_long_messages_at_the_512_byte_limit_on_char_boundaries() {
+        let connection = test_connection("reply-chunking", "nobody!nobody@host").await;
+        let mock = Arc::new(MockTransport::new());
+        connection.set_client(Box::new(mock.clone())).await;
+
+        // each "é" is 2 bytes in UTF-8, so a naive byte-based split could
+        // easily land in the middle of one.
+        let body: String = std::iter::repeat('é').take(400).collect();
+        connection.reply("#chan", &body).await;
+
+        let sent = mock.sent();
+        assert!(sent.len() > 1, "message should have been split into multiple lines");
+        for message in &sent {
+            let SentMessage::Privmsg { target, message } = message else {
+                panic!("reply should only ever send PRIVMSGs");
+            };
+            let overhead = target.len() + b"PRIVMSG   :\r\n".len();
+            assert!(message.len() + overhead <= 512);
+        }
+    }
 }
@@ -0,0 +1,388 @@
+//! Registry of top-level commands (`!name`, as opposed to the `ns.name`
+//! namespaced module commands `bot::handle_command` dispatches separately),
+//! replacing the hand-written `match` that used to live in
+//! `BotState::management_command`. Each command is a small value
+//! implementing [`Command`], registered once in
+//! [`CommandRegistry::with_defaults`]; adding a new one means writing a
+//! struct and a `register` call, not editing a match arm, and a module can
+//! register its own via [`CommandRegistry::register`] instead of only being
+//! reachable through its namespace.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use irc::proto::Prefix;
+use tracing::{error, info, warn};
+
+use crate::acl::Capability;
+use crate::bot::{BotState, UserMask};
+use crate::connection::Connection;
+
+/// Mirrors `rustico::webload::BoxFuture`: the standard way to return a
+/// `Future` from a trait method that's also called through `dyn Command`.
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// What a command wants sent back to `ctx.response_target`, if anything.
+pub(crate) enum Reply {
+    None,
+    Text(String),
+}
+
+impl From<String> for Reply {
+    fn from(value: String) -> Self {
+        Reply::Text(value)
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(value: &str) -> Self {
+        Reply::Text(value.to_string())
+    }
+}
+
+/// Everything a [`Command::run`] needs: who asked, which network they asked
+/// on, where to reply, and with what arguments.
+pub(crate) struct CommandCtx {
+    pub(crate) state: Arc<BotState>,
+    pub(crate) connection: Arc<Connection>,
+    pub(crate) source: Option<Prefix>,
+    /// The IRCv3 `account` message tag, if the network negotiated it and
+    /// the sender is logged in. Preferred over `source`'s hostmask by
+    /// [`check_capability`](Self::check_capability); see
+    /// [`crate::bot::Identity`].
+    pub(crate) account: Option<String>,
+    pub(crate) response_target: String,
+    pub(crate) args: String,
+}
+
+impl CommandCtx {
+    /// Whether `ctx.account`/`ctx.source` holds a role granting
+    /// `capability` on `ctx.connection`'s network.
+    pub(crate) async fn check_capability(&self, capability: Capability<'_>) -> bool {
+        self.connection
+            .check_capability(self.source.as_ref(), self.account.as_deref(), capability)
+            .await
+    }
+}
+
+/// A top-level command. Implement this and register it with a
+/// [`CommandRegistry`] to teach the bot a new `!name` without touching
+/// dispatch itself.
+pub(crate) trait Command: Send + Sync {
+    /// The name it's invoked by, e.g. `"trust"`.
+    fn name(&self) -> &'static str;
+
+    /// One-line usage shown by `help`. Defaults to just the bare name, for
+    /// commands that take no arguments.
+    fn syntax(&self) -> &str {
+        self.name()
+    }
+
+    /// The capability `ctx.source` must hold before dispatch will call
+    /// [`run`](Self::run) at all. Defaults to the command's own name, which
+    /// covers everything but `help`/`ping` (granted to the `public` role so
+    /// they keep working for anyone, same as before this registry existed).
+    fn required_capability(&self) -> Capability<'static> {
+        Capability::Command(self.name())
+    }
+
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply>;
+}
+
+/// The set of registered top-level commands, consulted by
+/// `BotState::dispatch_command`.
+pub(crate) struct CommandRegistry {
+    commands: HashMap<&'static str, Box<dyn Command>>,
+}
+
+impl CommandRegistry {
+    /// The built-in commands: `ping`, `join`, the trust/grant family,
+    /// `load`, `modinfo`, `permits`, `quit`, and `help`.
+    pub(crate) fn with_defaults() -> Self {
+        let mut registry = Self {
+            commands: HashMap::new(),
+        };
+        registry.register(Box::new(Ping));
+        registry.register(Box::new(Join));
+        registry.register(Box::new(Trust));
+        registry.register(Box::new(Untrust));
+        registry.register(Box::new(TrustList));
+        registry.register(Box::new(Grant));
+        registry.register(Box::new(Revoke));
+        registry.register(Box::new(Load));
+        registry.register(Box::new(ModInfo));
+        registry.register(Box::new(Permits));
+        registry.register(Box::new(Quit));
+        registry.register(Box::new(Help));
+        registry
+    }
+
+    /// Registers `command` under its own name, replacing any earlier
+    /// command registered with that name.
+    pub(crate) fn register(&mut self, command: Box<dyn Command>) {
+        self.commands.insert(command.name(), command);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&dyn Command> {
+        self.commands.get(name).map(Box::as_ref)
+    }
+
+    /// One line per registered command, sorted by name.
+    fn help_text(&self) -> String {
+        let mut lines: Vec<_> = self.commands.values().map(|c| c.syntax()).collect();
+        lines.sort_unstable();
+        lines.join("\n")
+    }
+}
+
+struct Ping;
+impl Command for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+    fn run<'a>(&'a self, _ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async { Reply::from("pong") })
+    }
+}
+
+struct Join;
+impl Command for Join {
+    fn name(&self) -> &'static str {
+        "join"
+    }
+    fn syntax(&self) -> &str {
+        "join <channel> [channel...]"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let chans: Vec<_> = ctx.args.split_whitespace().collect();
+            let _ = ctx.connection.client(|client| client.send_join(&chans.join(","), None));
+            Reply::None
+        })
+    }
+}
+
+/// Parses a `trust`/`untrust` argument, either a `nick!user@host` hostmask
+/// or an `account:<name>` pseudo-mask (see [`crate::bot::Identity::acl_key`])
+/// for trusting an authenticated services account regardless of which
+/// hostmask it connects from. Returns the ACL mask string to grant/revoke
+/// alongside a short label for the reply.
+fn parse_trust_target(arg: &str) -> Option<(String, String)> {
+    let arg = arg.trim();
+    if let Some(name) = arg.strip_prefix("account:").filter(|name| !name.is_empty()) {
+        return Some((format!("account:{name}"), name.to_string()));
+    }
+    let mask = arg.parse::<UserMask>().ok()?;
+    Some((mask.to_string(), mask.nick().to_string()))
+}
+
+struct Trust;
+impl Command for Trust {
+    fn name(&self) -> &'static str {
+        "trust"
+    }
+    fn syntax(&self) -> &str {
+        "trust <nick!user@host|account:<name>>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let Some((mask, label)) = parse_trust_target(&ctx.args) else {
+                warn!(args = ctx.args, "invalid trust target");
+                return Reply::None;
+            };
+            match ctx.connection.grant(&mask, "trusted").await {
+                Ok(true) => Reply::from(format!("I now trust {label}")),
+                Ok(false) => Reply::from(format!("I already trust {label}")),
+                Err(error) => {
+                    error!(%error, "grant failed");
+                    Reply::from("could not persist trust (check logs)")
+                }
+            }
+        })
+    }
+}
+
+struct Untrust;
+impl Command for Untrust {
+    fn name(&self) -> &'static str {
+        "untrust"
+    }
+    fn syntax(&self) -> &str {
+        "untrust <nick!user@host|account:<name>>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let Some((mask, label)) = parse_trust_target(&ctx.args) else {
+                warn!(args = ctx.args, "invalid trust target");
+                return Reply::None;
+            };
+            match ctx.connection.revoke(&mask, "trusted").await {
+                Ok(()) => Reply::from(format!("no longer trusting {label}")),
+                Err(error) => {
+                    error!(%error, "revoke failed");
+                    Reply::None
+                }
+            }
+        })
+    }
+}
+
+struct TrustList;
+impl Command for TrustList {
+    fn name(&self) -> &'static str {
+        "trust-list"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            match ctx.connection.trust_list().await {
+                Ok(grants) => info!(?grants, "trust-list"),
+                Err(error) => error!(%error, "could not list grants"),
+            }
+            Reply::None
+        })
+    }
+}
+
+struct Grant;
+impl Command for Grant {
+    fn name(&self) -> &'static str {
+        "grant"
+    }
+    fn syntax(&self) -> &str {
+        "grant <mask> <role>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let mut args = ctx.args.split_whitespace();
+            let (Some(mask), Some(role)) = (args.next(), args.next()) else {
+                warn!("usage: grant <mask> <role>");
+                return Reply::None;
+            };
+            match ctx.connection.grant(mask, role).await {
+                Ok(true) => Reply::from(format!("granted {role} to {mask}")),
+                Ok(false) => Reply::from(format!("{mask} already has {role}")),
+                Err(error) => {
+                    error!(%error, "grant failed");
+                    Reply::from(format!("could not grant {role} to {mask} (check logs)"))
+                }
+            }
+        })
+    }
+}
+
+struct Revoke;
+impl Command for Revoke {
+    fn name(&self) -> &'static str {
+        "revoke"
+    }
+    fn syntax(&self) -> &str {
+        "revoke <mask> <role>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let mut args = ctx.args.split_whitespace();
+            let (Some(mask), Some(role)) = (args.next(), args.next()) else {
+                warn!("usage: revoke <mask> <role>");
+                return Reply::None;
+            };
+            match ctx.connection.revoke(mask, role).await {
+                Ok(()) => Reply::from(format!("revoked {role} from {mask}")),
+                Err(error) => {
+                    error!(%error, "revoke failed");
+                    Reply::from(format!("could not revoke {role} from {mask} (check logs)"))
+                }
+            }
+        })
+    }
+}
+
+struct Load;
+impl Command for Load {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+    fn syntax(&self) -> &str {
+        "load <module>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let module_name = ctx.args.trim().to_string();
+            match ctx.state.rustico().load_module(module_name, false).await {
+                Ok(name) => match ctx.state.rustico().module_info(&name).await {
+                    Ok(rustico::ModuleInfo { version: Some(version), .. }) => {
+                        Reply::from(format!("loaded module: {name} (abi {version})"))
+                    }
+                    _ => Reply::from(format!("loaded module: {name}")),
+                },
+                Err(error) => {
+                    error!(%error, "management load failed");
+                    Reply::from("cannot load module (check logs)")
+                }
+            }
+        })
+    }
+}
+
+struct ModInfo;
+impl Command for ModInfo {
+    fn name(&self) -> &'static str {
+        "modinfo"
+    }
+    fn syntax(&self) -> &str {
+        "modinfo <name>"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            let module_name = ctx.args.trim();
+            match ctx.state.rustico().module_info(module_name).await {
+                Ok(info) => {
+                    let version = info.version.as_deref().unwrap_or("none declared");
+                    let capabilities = if info.capabilities.is_empty() {
+                        "none".to_string()
+                    } else {
+                        info.capabilities.join(", ")
+                    };
+                    Reply::from(format!(
+                        "{module_name}: abi version {version}, capabilities: {capabilities}"
+                    ))
+                }
+                Err(error) => Reply::from(format!("{module_name}: {error}")),
+            }
+        })
+    }
+}
+
+struct Permits;
+impl Command for Permits {
+    fn name(&self) -> &'static str {
+        "permits"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move { Reply::from(format!("available permits: {}", ctx.state.available_permits())) })
+    }
+}
+
+struct Quit;
+impl Command for Quit {
+    fn name(&self) -> &'static str {
+        "quit"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move {
+            ctx.connection.request_quit();
+            Reply::None
+        })
+    }
+}
+
+struct Help;
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+    fn run<'a>(&'a self, ctx: CommandCtx) -> BoxFuture<'a, Reply> {
+        Box::pin(async move { Reply::from(ctx.state.commands().help_text()) })
+    }
+}
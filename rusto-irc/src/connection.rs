@@ -0,0 +1,214 @@
+//! A single IRC network connection: its `Client`, its own ACL/trust scope
+//! and flood-control budgets, and the reconnect loop that keeps it alive
+//! independently of every other configured network. `BotState` holds one
+//! `Connection` per `[[network]]` entry; the shared engine semaphore, epoch
+//! timer, and command registry live on `BotState` instead, since those apply
+//! across every network a single wotto process serves.
+
+use std::fmt::Debug;
+
+use irc::client::prelude::Config;
+use irc::proto::Prefix;
+use tokio::sync::RwLock;
+use tracing::error;
+
+use crate::acl::{Acl, AclError, Capability};
+use crate::backoff::Backoff;
+use crate::bot::{Identity, UserMask};
+use crate::flood::{Budget, FloodControl};
+use crate::network::NetworkId;
+use crate::shutdown::Shutdown;
+use crate::throttling::Throttler;
+use crate::transport::IrcTransport;
+
+/// Default per-user command budget, for masks without the `trusted` role:
+/// at most 5 commands per 10 seconds.
+const DEFAULT_FLOOD_BUDGET: Budget = Budget::new(5, std::time::Duration::from_secs(10));
+/// Budget for masks holding the `trusted` role: at most 20 commands per 10
+/// seconds.
+const TRUSTED_FLOOD_BUDGET: Budget = Budget::new(20, std::time::Duration::from_secs(10));
+
+pub(crate) struct Connection {
+    id: NetworkId,
+    config: Config,
+    client: RwLock<Option<Box<dyn IrcTransport>>>,
+    acl: Acl,
+    flood: FloodControl,
+    throttler: Throttler,
+    /// Fires when this network (and only this network) should stop
+    /// reconnecting, e.g. because its `quit` command ran.
+    quitting: Shutdown,
+    /// Consecutive reconnect failures on this network, and the delay to
+    /// wait before the next attempt; see `crate::bot::connection_task`.
+    backoff: Backoff,
+}
+
+impl Connection {
+    /// Opens this network's own ACL database (`wotto-acl-<id>.db`, so trust
+    /// granted on one network never leaks into another) and flood budgets,
+    /// both scoped to `config`.
+    pub(crate) async fn connect(id: NetworkId, config: Config) -> Result<Self, AclError> {
+        let acl = Acl::connect(&format!("wotto-acl-{}.db", id.as_str()), &config).await?;
+        let flood = FloodControl::new(
+            budget_from_config(&config, "flood_limit", "flood_window_secs", DEFAULT_FLOOD_BUDGET),
+            budget_from_config(
+                &config,
+                "trusted_flood_limit",
+                "trusted_flood_window_secs",
+                TRUSTED_FLOOD_BUDGET,
+            ),
+        );
+        let throttler = Throttler::make()
+            .layer(5, 2500)
+            .layer(2, 150)
+            .layer(1, 50)
+            .build();
+        Ok(Self {
+            id,
+            config,
+            client: RwLock::new(None),
+            acl,
+            flood,
+            throttler,
+            quitting: Shutdown::new(),
+            backoff: Backoff::new(),
+        })
+    }
+
+    pub(crate) fn id(&self) -> &NetworkId {
+        &self.id
+    }
+
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub(crate) fn client<F, T>(&self, f: F) -> Option<T>
+    where
+        F: FnOnce(&dyn IrcTransport) -> T,
+    {
+        match self.client.try_read() {
+            Ok(guard) => guard.as_deref().map(f),
+            Err(_) => None,
+        }
+    }
+
+    pub(crate) async fn set_client(&self, client: Box<dyn IrcTransport>) {
+        *self.client.write().await = Some(client);
+    }
+
+    pub(crate) async fn trust_list(&self) -> Result<Vec<(String, String)>, AclError> {
+        self.acl.list_grants().await
+    }
+
+    pub(crate) async fn grant(&self, mask: &str, role: &str) -> Result<bool, AclError> {
+        self.acl.grant(mask, role).await
+    }
+
+    pub(crate) async fn revoke(&self, mask: &str, role: &str) -> Result<(), AclError> {
+        self.acl.revoke(mask, role).await
+    }
+
+    /// Whether the sender holds a role granting `capability` on this
+    /// network. `account` (the IRCv3 `account` message tag, if the network
+    /// negotiated it and the sender is logged in) is preferred over
+    /// `prefix`'s hostmask, since the latter is spoofable; `None` for both
+    /// (a server-sourced message, or a hostmask that failed to parse) is
+    /// never trusted with anything. See [`Identity::resolve`].
+    pub(crate) async fn check_capability(
+        &self,
+        prefix: Option<&Prefix>,
+        account: Option<&str>,
+        capability: Capability<'_>,
+    ) -> bool {
+        let Some(identity) = Identity::resolve(prefix, account) else { return false; };
+        match self.acl.check_capability(&identity.acl_key(), capability).await {
+            Ok(allowed) => allowed,
+            Err(error) => {
+                error!(id = %self.id, %error, "acl check failed");
+                false
+            }
+        }
+    }
+
+    /// Whether `prefix` is still within its per-user command budget on
+    /// this network; if so, this counts towards it.
+    pub(crate) async fn check_flood(&self, prefix: Option<&Prefix>) -> bool {
+        let Some(prefix) = prefix else { return true; };
+        let Ok(mask): Result<UserMask, _> = prefix.try_into() else { return true; };
+        let trusted = match self.acl.is_trusted(&mask.to_string()).await {
+            Ok(trusted) => trusted,
+            Err(error) => {
+                error!(id = %self.id, %error, "acl check failed");
+                false
+            }
+        };
+        self.flood.check(&mask, trusted).await
+    }
+
+    #[tracing::instrument]
+    pub(crate) async fn reply<R: AsRef<str> + Debug, M: AsRef<str> + Debug>(&self, response_target: R, message: M) {
+        const MAX_SIZE: usize = 512;
+        let target = response_target.as_ref();
+        let message = message.as_ref();
+
+        for (i, line) in message
+            .split_terminator(|c| c == '\r' || c == '\n')
+            .filter(|x| !x.is_empty())
+            .enumerate()
+        {
+            let prefix = if i == 0 { "\x02>\x0f" } else { "\x02:\x0f" };
+            let line = format!("{prefix}{line}");
+            let overhead = target.bytes().len() + b"PRIVMSG   :\r\n".len();
+            let max_payload_size = MAX_SIZE.saturating_sub(overhead);
+            let boundary = line.floor_char_boundary(max_payload_size);
+            self.throttler.acquire_one().await;
+            let _ = self.client(|client| client.send_privmsg(target, &line[..boundary]));
+        }
+    }
+
+    /// Stops this network's reconnect loop and sends `QUIT`, without
+    /// touching any other configured network.
+    pub(crate) fn request_quit(&self) {
+        if !self.quitting.is_tripped() {
+            self.quitting.fire();
+            let _ = self.client(|client| client.send_quit("requested"));
+        }
+    }
+
+    pub(crate) fn is_quitting(&self) -> bool {
+        self.quitting.is_tripped()
+    }
+
+    pub(crate) fn quitting(&self) -> Shutdown {
+        self.quitting.clone()
+    }
+
+    pub(crate) fn backoff(&self) -> &Backoff {
+        &self.backoff
+    }
+}
+
+impl core::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("id", &self.id)
+            .field("quitting", &self.quitting.is_tripped())
+            .finish()
+    }
+}
+
+/// Reads `limit_option`/`window_option` from `config`, falling back to
+/// `default` piecewise if either is missing or doesn't parse.
+fn budget_from_config(config: &Config, limit_option: &str, window_option: &str, default: Budget) -> Budget {
+    let limit = config
+        .get_option(limit_option)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default.limit);
+    let window = config
+        .get_option(window_option)
+        .and_then(|s| s.parse().ok())
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(default.window);
+    Budget::new(limit, window)
+}
@@ -0,0 +1,264 @@
+//! Typed control protocol served over two transports: `web_server`'s
+//! `/control` websocket route, and (see `crate::control_socket`) a Unix
+//! domain socket for host-local administration. Unlike the fire-and-forget
+//! `/load`/`/join` HTTP routes (which only ever report their result to
+//! stderr), both get back the actual result of each request, and can
+//! subscribe to a live feed of incoming IRC commands and the output they
+//! produced. [`RequestKind`]/[`ResponseKind`] and [`dispatch`] are shared by
+//! both transports; only how a request/response is framed on the wire (one
+//! websocket message vs. one line of a Unix socket stream) differs.
+//!
+//! `/control` is also the full-duplex event gateway: it supersedes having a
+//! separate `/ws` route, since a `subscribe` request turns the very same
+//! connection into a live feed without giving up the ability to keep
+//! sending requests on it. A client that falls behind the broadcast feed
+//! (see [`handle_socket`]) has its subscription dropped rather than kept
+//! open on stale or skipped events.
+//!
+//! Every message in either direction is one JSON object. Requests are
+//! `{"id": ..., "type": ..., ...fields}`; responses echo the same `id` so a
+//! client can match them up. Over the websocket, the first request must be
+//! `auth` with the token from the bot's `control_token` config option, and
+//! every other request is rejected until that succeeds; the Unix socket
+//! skips this, since reaching it at all already implies host-level access
+//! (see `crate::control_socket`).
+
+use std::sync::Weak;
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+use warp::ws::{Message, WebSocket};
+
+use crate::bot::{constant_time_eq, BotState};
+
+/// One update pushed to subscribed control sockets.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Event {
+    Command { network: String, source: Option<String>, command: String },
+    ModuleOutput { network: String, module: String, output: String },
+    /// A channel or private `PRIVMSG`, whether or not it happened to parse
+    /// as a bot command — lets a dashboard show ordinary channel traffic,
+    /// not just commands.
+    Privmsg { network: String, source: Option<String>, target: String, text: String },
+    Notice { network: String, source: Option<String>, target: String, text: String },
+    Join { network: String, source: Option<String>, channel: String },
+    Part { network: String, source: Option<String>, channel: String, reason: Option<String> },
+    NickChange { network: String, source: Option<String>, new_nick: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RequestContainer {
+    pub(crate) id: u64,
+    #[serde(flatten)]
+    pub(crate) kind: RequestKind,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum RequestKind {
+    Auth { token: String },
+    Load { module: String },
+    Join { network: String, channel: String },
+    /// Sends a `PRIVMSG` on `network` as the bot, the same as the `say`
+    /// path a dashboard would otherwise need a full IRC client to drive.
+    Say { network: String, target: String, message: String },
+    TrustList { network: String },
+    Permits,
+    Subscribe,
+    /// Quits one network, or (with no `network`) the whole bot.
+    Quit { network: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ResponseContainer {
+    pub(crate) id: u64,
+    #[serde(flatten)]
+    pub(crate) kind: ResponseKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ResponseKind {
+    AuthOk,
+    Loaded { module: String },
+    Joined { network: String, channel: String },
+    JoinFailed { network: String, channel: String, error: String },
+    Sent { network: String },
+    TrustList { network: String, grants: Vec<(String, String)> },
+    Permits { available: usize },
+    Subscribed,
+    Quitting { network: Option<String> },
+    Error { message: String },
+}
+
+pub(crate) fn respond(id: u64, kind: ResponseKind) -> Message {
+    let container = ResponseContainer { id, kind };
+    Message::text(serde_json::to_string(&container).expect("ResponseContainer always serializes"))
+}
+
+fn event_message(event: &Event) -> Message {
+    Message::text(serde_json::to_string(event).expect("Event always serializes"))
+}
+
+/// Drives one `/control` connection end to end: authentication, request
+/// dispatch, and (once `subscribe` is requested) forwarding the bot's event
+/// feed until the socket or the bot itself goes away.
+pub(crate) async fn handle_socket(
+    socket: WebSocket,
+    state: Weak<BotState>,
+    expected_token: Option<String>,
+) {
+    let (mut tx, mut rx) = socket.split();
+    let mut authed = expected_token.is_none();
+    let mut events: Option<broadcast::Receiver<Event>> = None;
+
+    loop {
+        let next_event = async {
+            match events.as_mut() {
+                Some(receiver) => receiver.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            message = rx.next() => {
+                let Some(Ok(message)) = message else { break; };
+                if !message.is_text() {
+                    continue;
+                }
+                let Ok(request) = serde_json::from_str::<RequestContainer>(message.to_str().unwrap_or_default()) else {
+                    continue;
+                };
+
+                if let RequestKind::Auth { token } = &request.kind {
+                    authed = match &expected_token {
+                        Some(expected) => constant_time_eq(token.as_bytes(), expected.as_bytes()),
+                        None => false,
+                    };
+                    let response = if authed {
+                        ResponseKind::AuthOk
+                    } else {
+                        ResponseKind::Error { message: "invalid token".to_string() }
+                    };
+                    if tx.send(respond(request.id, response)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
+                if !authed {
+                    let response = ResponseKind::Error { message: "not authenticated".to_string() };
+                    if tx.send(respond(request.id, response)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
+                let Some(state) = state.upgrade() else { break; };
+                let response = dispatch(&state, request.kind, &mut events).await;
+                if tx.send(respond(request.id, response)).await.is_err() {
+                    break;
+                }
+            }
+            event = next_event => {
+                match event {
+                    Ok(event) => {
+                        if tx.send(event_message(&event)).await.is_err() {
+                            break;
+                        }
+                    }
+                    // this client is too slow to keep up with the broadcast
+                    // feed; rather than block the IRC read loop (the sender)
+                    // on a lagging socket, drop its subscription instead of
+                    // letting it silently skip events forever. It can send
+                    // another `subscribe` request to pick back up from the
+                    // current tail.
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "control socket: event feed lagged, ending subscription");
+                        events = None;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        events = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs one already-authenticated request against `state`. Shared by the
+/// websocket and Unix socket transports; `events` is only ever populated by
+/// [`RequestKind::Subscribe`], so a caller that can't forward an event feed
+/// (a one-shot request/response transport) can simply pass `&mut None` and
+/// ignore it.
+pub(crate) async fn dispatch(
+    state: &BotState,
+    request: RequestKind,
+    events: &mut Option<broadcast::Receiver<Event>>,
+) -> ResponseKind {
+    match request {
+        RequestKind::Auth { .. } => unreachable!("authentication is handled by the caller"),
+        RequestKind::Load { module } => match state.rustico().load_module(module, false).await {
+            Ok(module) => ResponseKind::Loaded { module },
+            Err(error) => ResponseKind::Error { message: error.to_string() },
+        },
+        RequestKind::Join { network, channel } => {
+            let Some(connection) = state.connection(&network) else {
+                return ResponseKind::JoinFailed {
+                    network,
+                    channel,
+                    error: "no such network".to_string(),
+                };
+            };
+            match connection.client(|client| client.send_join(&channel, None)) {
+                Some(Ok(())) => ResponseKind::Joined { network, channel },
+                Some(Err(error)) => ResponseKind::JoinFailed { network, channel, error: error.to_string() },
+                None => ResponseKind::JoinFailed {
+                    network,
+                    channel,
+                    error: "not connected".to_string(),
+                },
+            }
+        }
+        RequestKind::Say { network, target, message } => {
+            let Some(connection) = state.connection(&network) else {
+                return ResponseKind::Error { message: "no such network".to_string() };
+            };
+            match connection.client(|client| client.send_privmsg(&target, &message)) {
+                Some(Ok(())) => ResponseKind::Sent { network },
+                Some(Err(error)) => ResponseKind::Error { message: error.to_string() },
+                None => ResponseKind::Error { message: "not connected".to_string() },
+            }
+        }
+        RequestKind::TrustList { network } => {
+            let Some(connection) = state.connection(&network) else {
+                return ResponseKind::Error { message: "no such network".to_string() };
+            };
+            match connection.trust_list().await {
+                Ok(grants) => ResponseKind::TrustList { network, grants },
+                Err(error) => ResponseKind::Error { message: error.to_string() },
+            }
+        }
+        RequestKind::Permits => ResponseKind::Permits {
+            available: state.available_permits(),
+        },
+        RequestKind::Subscribe => {
+            *events = Some(state.subscribe_events());
+            ResponseKind::Subscribed
+        }
+        RequestKind::Quit { network: Some(network) } => {
+            let Some(connection) = state.connection(&network) else {
+                return ResponseKind::Error { message: "no such network".to_string() };
+            };
+            connection.request_quit();
+            ResponseKind::Quitting { network: Some(network) }
+        }
+        RequestKind::Quit { network: None } => {
+            state.request_shutdown();
+            ResponseKind::Quitting { network: None }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Unix domain socket admin interface, reusing `crate::control`'s shared
+//! request/response protocol but framed as one JSON object per line instead
+//! of one websocket message. Unlike the `/control` websocket route, this is
+//! host-local and deliberately unauthenticated: reaching the socket file at
+//! all already implies whatever access its filesystem permissions grant, so
+//! there's no `auth` handshake and every request bypasses IRC-side trust
+//! entirely. Opt-in via `rusto.toml`'s `[bot] control_socket_path` — absent
+//! means [`serve`] is never spawned, same as an unset `control_token` leaves
+//! the websocket route open instead of opt-in (the two settings trade off
+//! differently, since a Unix socket can be permissioned at the filesystem
+//! level in a way a websocket route can't).
+//!
+//! This gives a scriptable admin surface independent of IRC connectivity —
+//! `load`/`trust-list`/`permits`/`quit` all still work while every
+//! configured network is disconnected or reconnecting; `join` is still
+//! answered then, with a `join_failed` response.
+
+use std::sync::Weak;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::bot::BotState;
+use crate::control::{self, Event, RequestContainer, RequestKind, ResponseContainer, ResponseKind};
+use crate::shutdown::Shutdown;
+
+/// Binds `path` (removing any stale socket file left over from an unclean
+/// exit first) and serves connections, one task per connection, until
+/// `shutdown` fires. Spawned by `bot_main` next to `web_server` whenever
+/// `control_socket_path` is configured.
+pub(crate) async fn serve(path: String, state: Weak<BotState>, shutdown: Shutdown) {
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(error) => {
+            error!(path, %error, "control socket: could not bind");
+            return;
+        }
+    };
+    info!(path, "control socket listening");
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_connection(stream, state.clone()));
+                    }
+                    Err(error) => error!(%error, "control socket: accept failed"),
+                }
+            }
+            _ = shutdown.tripped() => break,
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Drives one connection: reads one JSON request per line, dispatches it
+/// through [`control::dispatch`], and writes back one JSON response per
+/// line, forwarding the event feed too if `subscribe` was requested — the
+/// same deal as [`control::handle_socket`], minus the `auth` step.
+async fn handle_connection(stream: UnixStream, state: Weak<BotState>) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut lines = BufReader::new(read_half).lines();
+    let mut events: Option<broadcast::Receiver<Event>> = None;
+
+    loop {
+        let next_event = async {
+            match events.as_mut() {
+                Some(receiver) => receiver.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            line = lines.next_line() => {
+                let Ok(Some(line)) = line else { break; };
+                let Ok(request) = serde_json::from_str::<RequestContainer>(&line) else { continue; };
+
+                let response = if let RequestKind::Auth { .. } = &request.kind {
+                    // no token to check here; accepted on principle that
+                    // reaching this socket at all already authorized the
+                    // caller. Handled so a client written against the
+                    // websocket protocol doesn't have to special-case us.
+                    ResponseKind::AuthOk
+                } else {
+                    let Some(state) = state.upgrade() else { break; };
+                    control::dispatch(&state, request.kind, &mut events).await
+                };
+                let container = ResponseContainer { id: request.id, kind: response };
+                if write_line(&mut write_half, container).await.is_err() {
+                    break;
+                }
+            }
+            event = next_event => {
+                match event {
+                    Ok(event) => {
+                        if write_line(&mut write_half, event).await.is_err() {
+                            break;
+                        }
+                    }
+                    // this client is too slow to keep up with the broadcast
+                    // feed; rather than block the IRC read loop (the sender)
+                    // on a lagging reader, drop its subscription instead of
+                    // letting it silently skip events forever, matching
+                    // `control::handle_socket`'s matching arm. It can send
+                    // another `subscribe` request to pick back up from the
+                    // current tail.
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "control socket: event feed lagged, ending subscription");
+                        events = None;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => events = None,
+                }
+            }
+        }
+    }
+}
+
+/// Serializes `value` and writes it as one `\n`-terminated line.
+async fn write_line<T: serde::Serialize>(
+    write_half: &mut tokio::net::unix::OwnedWriteHalf,
+    value: T,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(&value).expect("control protocol types always serialize");
+    line.push('\n');
+    write_half.write_all(line.as_bytes()).await
+}
@@ -0,0 +1,79 @@
+//! Per-user flood control, independent of [`crate::throttling::Throttler`]:
+//! that only rate-limits outbound `PRIVMSG` lines globally, so nothing stops
+//! one user from queueing more module invocations than the
+//! `engine_semaphore` permits (two by default) can keep up with. This tracks a sliding window
+//! of command timestamps per mask and refuses dispatch once a user's budget
+//! for the window is spent, pruning idle masks as it goes so the map doesn't
+//! grow unbounded.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use crate::bot::UserMask;
+
+/// At most `limit` commands per `window`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Budget {
+    pub(crate) limit: usize,
+    pub(crate) window: Duration,
+}
+
+impl Budget {
+    pub(crate) const fn new(limit: usize, window: Duration) -> Self {
+        Self { limit, window }
+    }
+}
+
+/// Per-mask sliding-window command budgets. `trusted_budget` is the one
+/// checked for masks [`crate::acl::Acl::is_trusted`] reports as trusted;
+/// everyone else gets `default_budget`.
+pub(crate) struct FloodControl {
+    default_budget: Budget,
+    trusted_budget: Budget,
+    history: Mutex<HashMap<UserMask, VecDeque<Instant>>>,
+}
+
+impl FloodControl {
+    pub(crate) fn new(default_budget: Budget, trusted_budget: Budget) -> Self {
+        Self {
+            default_budget,
+            trusted_budget,
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records one command from `mask` and reports whether it's still
+    /// within budget (and should be let through).
+    pub(crate) async fn check(&self, mask: &UserMask, trusted: bool) -> bool {
+        let budget = if trusted { self.trusted_budget } else { self.default_budget };
+        let now = Instant::now();
+        let oldest_relevant = now.checked_sub(self.widest_window()).unwrap_or(now);
+
+        let mut history = self.history.lock().await;
+        // every call is a good opportunity to drop masks that haven't shown
+        // up in a while, so the map doesn't grow unbounded with one-off
+        // visitors.
+        history.retain(|_, timestamps| {
+            while timestamps.front().is_some_and(|t| *t < oldest_relevant) {
+                timestamps.pop_front();
+            }
+            !timestamps.is_empty()
+        });
+
+        let timestamps = history.entry(mask.clone()).or_default();
+        while timestamps.front().is_some_and(|t| now.duration_since(*t) > budget.window) {
+            timestamps.pop_front();
+        }
+        if timestamps.len() >= budget.limit {
+            return false;
+        }
+        timestamps.push_back(now);
+        true
+    }
+
+    fn widest_window(&self) -> Duration {
+        self.default_budget.window.max(self.trusted_budget.window)
+    }
+}
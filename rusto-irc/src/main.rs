@@ -1,10 +1,20 @@
 #![feature(round_char_boundary)]
 #![feature(arbitrary_self_types)]
 
+mod acl;
+mod backoff;
 mod bot;
+mod commands;
+mod connection;
+mod control;
+mod control_socket;
+mod flood;
+mod network;
 mod parsing;
+mod shutdown;
 mod throttling;
 mod tracing;
+mod transport;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -0,0 +1,111 @@
+//! Loads `rusto.toml`'s `[[network]]` array: each entry names a network and
+//! points at the full `irc::client::prelude::Config` file describing how to
+//! connect to it (the same format `Config::load` already understood back
+//! when the bot only ever served one network, so adding a network is "drop
+//! a file, add one `[[network]]` entry", not learning a new schema). A
+//! `[bot]` section alongside it holds the handful of settings that apply
+//! across every network rather than to one connection in particular.
+
+use irc::client::prelude::Config as IrcConfig;
+use serde::Deserialize;
+
+/// Identifies one configured network (e.g. `"libera"`), used to key
+/// `BotState`'s connections and to address `reply`/`request_quit`/the web
+/// `/join` route at a specific one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct NetworkId(String);
+
+impl NetworkId {
+    pub(crate) fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for NetworkId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::borrow::Borrow<str> for NetworkId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub(crate) struct BotOptions {
+    /// The token an operator's `/control` socket must present; see
+    /// `crate::control`. No token means the socket auto-authenticates.
+    pub(crate) control_token: Option<String>,
+    /// How many module invocations may run concurrently across every
+    /// network; see `BotState`'s `engine_semaphore`. Defaults to
+    /// `BotState::DEFAULT_ENGINE_PERMITS` when absent.
+    pub(crate) engine_permits: Option<usize>,
+    /// Path to listen on for `crate::control_socket`'s Unix domain socket
+    /// admin interface. Absent means the socket isn't opened at all — it's
+    /// opt-in, since reaching it bypasses every IRC-side trust check.
+    pub(crate) control_socket_path: Option<String>,
+    /// Host `web_server` binds to. Defaults to `127.0.0.1` (loopback-only)
+    /// when absent.
+    pub(crate) web_bind_host: Option<String>,
+    /// Port `web_server` binds to. Defaults to `3030` when absent.
+    pub(crate) web_bind_port: Option<u16>,
+    /// Paths to a PEM certificate and private key. Both must be set
+    /// together for `web_server` to terminate TLS (serving the control API
+    /// and websocket gateway as HTTPS/WSS) instead of plaintext; see
+    /// `crate::bot::WebBindConfig`.
+    pub(crate) web_tls_cert_path: Option<String>,
+    pub(crate) web_tls_key_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    bot: BotOptions,
+    network: Vec<NetworkEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetworkEntry {
+    id: String,
+    config: String,
+}
+
+pub(crate) struct NetworksConfig {
+    pub(crate) bot: BotOptions,
+    pub(crate) networks: Vec<(NetworkId, IrcConfig)>,
+}
+
+/// Reads `path` (normally `rusto.toml`) and loads each `[[network]]`
+/// entry's own `irc::client::prelude::Config` file, in declaration order.
+pub(crate) fn load(path: &str) -> Result<NetworksConfig, Box<dyn std::error::Error>> {
+    let manifest = std::fs::read_to_string(path)?;
+    let manifest: Manifest = toml::from_str(&manifest)?;
+    let networks = manifest
+        .network
+        .into_iter()
+        .map(|entry| -> Result<_, Box<dyn std::error::Error>> {
+            let config = IrcConfig::load(&entry.config)?;
+            Ok((NetworkId(entry.id), config))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(NetworksConfig {
+        bot: manifest.bot,
+        networks,
+    })
+}
+
+/// Re-reads just `path`'s `[bot]` section, without touching any
+/// per-network config file — cheap enough to call on every tick of a
+/// config-watcher loop, unlike [`load`], which also opens every network's
+/// own `Config::load`.
+pub(crate) fn load_bot_options(path: &str) -> Result<BotOptions, Box<dyn std::error::Error>> {
+    let manifest = std::fs::read_to_string(path)?;
+    let manifest: Manifest = toml::from_str(&manifest)?;
+    Ok(manifest.bot)
+}
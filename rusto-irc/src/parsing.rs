@@ -1,10 +1,13 @@
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, alphanumeric1, hex_digit1, one_of, satisfy, space0};
-use nom::combinator::{eof, map, recognize};
-use nom::multi::{count, many0, many0_count, many1};
+use nom::character::complete::{
+    alpha1, alphanumeric1, char, hex_digit1, one_of, satisfy, space0, space1,
+};
+use nom::combinator::{eof, map, opt, recognize};
+use nom::multi::{count, many0, many0_count, many1, separated_list1};
 use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, Tuple};
 use nom::{Finish, IResult};
+use rusto_utils::escape::escape_str;
 
 use crate::{BotCommand, CommandName};
 
@@ -81,20 +84,108 @@ fn user(input: &str) -> IResult<&str, &str> {
     recognize(many1(satisfy(|c| c.is_ascii_graphic() && c != '@')))(input)
 }
 
-fn host(input: &str) -> IResult<&str, &str> {
-    // TODO better validation for hosts
+/// Forbidden host code points from the WHATWG URL host-parsing algorithm:
+/// control characters, space, and a fixed set of syntax-significant
+/// punctuation that would make a hostname ambiguous with the rest of the
+/// message it's embedded in.
+fn has_forbidden_host_code_point(s: &str) -> bool {
+    s.chars().any(|c| {
+        matches!(
+            c,
+            '\0'..='\u{1F}'
+                | ' '
+                | '#'
+                | '%'
+                | '/'
+                | ':'
+                | '<'
+                | '>'
+                | '?'
+                | '@'
+                | '['
+                | '\\'
+                | ']'
+                | '^'
+                | '|'
+                | '\u{7F}'
+        )
+    })
+}
+
+/// Percent-decodes `%XX` escapes in `raw` into UTF-8, leaving any byte that
+/// isn't part of a valid escape untouched. IRC hostnames aren't normally
+/// percent-encoded, but the WHATWG host algorithm this mirrors always
+/// decodes first, so a host that happens to arrive encoded is still
+/// validated against its real content rather than its escaped spelling.
+fn percent_decode(raw: &str) -> std::borrow::Cow<'_, str> {
+    if !raw.contains('%') {
+        return std::borrow::Cow::Borrowed(raw);
+    }
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 3 <= bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            if let Ok(byte) = u8::from_str_radix(&raw[i + 1..i + 3], 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    match String::from_utf8(out) {
+        Ok(decoded) => std::borrow::Cow::Owned(decoded),
+        Err(_) => std::borrow::Cow::Borrowed(raw),
+    }
+}
+
+/// Percent-decodes `raw`, runs it through IDNA to produce a punycode A-label
+/// ASCII form, and rejects anything containing a forbidden host code point —
+/// the WHATWG URL host-parsing algorithm, which accepts real-world
+/// internationalized domain names without opening the homoglyph/bidi
+/// concerns flagged on [`nickname`] (the A-label form is pure ASCII, so
+/// there's nothing left to spoof with).
+fn validate_hostname(raw: &str) -> Option<String> {
+    let decoded = percent_decode(raw);
+    let ascii = idna::domain_to_ascii(&decoded).ok()?;
+    if ascii.is_empty() || has_forbidden_host_code_point(&ascii) {
+        return None;
+    }
+    Some(ascii)
+}
 
+/// WHATWG's "ends in a number" check: true if `host`'s last `.`-separated
+/// label is fully numeric or a `0x`/`0X`-prefixed hex number. Deciding this
+/// before attempting IPv4 parsing is what keeps a hostname like
+/// `example.com` (last label `com`) from ever being misparsed as an address.
+fn ends_in_number(host: &str) -> bool {
+    let last = host.rsplit('.').next().unwrap_or(host);
+    if !last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+    match last.strip_prefix("0x").or_else(|| last.strip_prefix("0X")) {
+        Some(digits) => !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+fn host(input: &str) -> IResult<&str, String> {
     // host       =  hostname / hostaddr
-    // hostname   =  shortname *( "." shortname )
-    // shortname  =  ( letter / digit ) *( letter / digit / "-" )
-    //               *( letter / digit )
-    //                 ; as specified in RFC 1123 [HNAME]
+    // hostname   =  a WHATWG-style internationalized domain name, validated
+    //               and normalized by `validate_hostname` rather than the
+    //               RFC 1123 `shortname`/`hostname` grammar, which is
+    //               ASCII-only.
     // hostaddr   =  ip4addr / ip6addr
     // ip4addr    =  1*3digit "." 1*3digit "." 1*3digit "." 1*3digit
     // ip6addr    =  1*hexdigit 7( ":" 1*hexdigit )
     // ip6addr    =/ "0:0:0:0:0:" ( "0" / "FFFF" ) ":" ip4addr
 
-    use nom::character::complete::char;
     use nom::character::complete::u8 as u8_;
     use nom::sequence::tuple;
     let ip4addr = |i| {
@@ -115,24 +206,31 @@ fn host(input: &str) -> IResult<&str, &str> {
             ))),
         ))(i)
     };
-    let hostaddr = move |i| alt((ip4addr, ip6addr))(i);
-    let shortname = |i| {
-        recognize(pair(
-            alphanumeric1,
-            many0_count(alt((alphanumeric1, tag("-")))),
-        ))(i)
-    };
-    let hostname = move |i| recognize(pair(shortname, many0(pair(tag("."), shortname))))(i);
+    let mut hostaddr = move |i| alt((ip4addr, ip6addr))(i);
 
-    alt((terminated(hostname, eof), terminated(hostaddr, eof)))(input)
+    // A host token runs up to the next whitespace (or the end of input, in
+    // `terminated(host, eof)` callers like `user_prefix`); it's never itself
+    // responsible for knowing what follows it.
+    let (remaining, raw): (&str, &str) =
+        recognize(many1(satisfy(|c| !matches!(c, ' ' | '\0' | '\r' | '\n'))))(input)?;
+    // an IPv6 literal never ends in a number, but its `:` (a forbidden host
+    // code point) already rules it out as a hostname
+    if ends_in_number(raw) || raw.contains(':') {
+        if let Ok((_, addr)) = terminated(&mut hostaddr, eof)(raw) {
+            return Ok((remaining, addr.to_string()));
+        }
+    }
+    match validate_hostname(raw) {
+        Some(ascii) => Ok((remaining, ascii)),
+        None => Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify))),
+    }
 }
 
 /// Parse `nick!user@host` style prefixes.
-pub(super) fn user_prefix(input: &str) -> Result<(&str, &str, &str), nom::error::Error<&str>> {
+pub(super) fn user_prefix(input: &str) -> Result<(&str, &str, String), nom::error::Error<&str>> {
     // let (_, ((nick, user), host)) =
     //     (separated_pair(separated_pair(nickname, tag("!"), user), tag("@"), host))(input)
     //         .finish()?;
-    use nom::character::complete::char;
     let (_input, (nick, user, host)) = (
         terminated(nickname, char('!')),
         terminated(user, char('@')),
@@ -143,6 +241,187 @@ pub(super) fn user_prefix(input: &str) -> Result<(&str, &str, &str), nom::error:
     Ok((nick, user, host))
 }
 
+/// Where a [`Message`] came from: either a bare server name, or a full
+/// `nick[!user]@host` mask, built from the same pieces as [`user_prefix`]
+/// (which itself always takes a `!user`). `user`/`host` are
+/// optional here because some servers (and most client-to-server traffic)
+/// send a source with no userhost at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Source {
+    Server(String),
+    User {
+        nick: String,
+        user: Option<String>,
+        host: String,
+    },
+}
+
+/// A fully parsed IRC line: optional IRCv3 tags, an optional source prefix,
+/// a command token, and its parameters. Unlike [`command`], which only
+/// understands the bot's own `!command` syntax, this parses the full
+/// RFC1459/2812 wire format of lines read off the connection. A source's
+/// nick is matched with [`nickname`]'s RFC 2812 subset, so a line from a
+/// nick outside it (one containing `-`, say) fails with
+/// [`MessageParseError::InvalidSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Message {
+    pub(crate) tags: Vec<(String, Option<String>)>,
+    pub(crate) source: Option<Source>,
+    pub(crate) command: String,
+    pub(crate) params: Vec<String>,
+}
+
+/// Why [`message`] couldn't parse a line, in place of the raw
+/// `nom::error::Error` the smaller parsers above surface.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum MessageParseError {
+    #[error("invalid message tags")]
+    InvalidTags,
+    #[error("invalid source prefix")]
+    InvalidSource,
+    #[error("invalid command token")]
+    InvalidCommand,
+    #[error("invalid message parameters")]
+    InvalidParams,
+    #[error("unconsumed input after message: \"{}\"", escape_str(.0))]
+    TrailingInput(String),
+}
+
+/// A tag's raw, still-escaped value: any octet except NUL, CR, LF, space and
+/// `;`, those being reserved to delimit tags from each other and from the
+/// rest of the message.
+fn tag_raw_value(input: &str) -> IResult<&str, &str> {
+    recognize(many0(satisfy(|c| {
+        !matches!(c, ' ' | ';' | '\0' | '\r' | '\n')
+    })))(input)
+}
+
+/// Undoes the IRCv3 message-tags escaping: `\:` -> `;`, `\s` -> space,
+/// `\\` -> `\`, `\r`/`\n` -> CR/LF, any other escaped character is taken
+/// literally (dropping the backslash), and a trailing unescaped `\` is
+/// dropped.
+fn unescape_tag_value(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('\\') => out.push('\\'),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn tag_key(input: &str) -> IResult<&str, &str> {
+    recognize(many1(satisfy(|c| {
+        !matches!(c, ' ' | ';' | '=' | '\0' | '\r' | '\n')
+    })))(input)
+}
+
+fn tag(input: &str) -> IResult<&str, (String, Option<String>)> {
+    let (input, (key, raw_value)) =
+        (tag_key, opt(preceded(char('='), tag_raw_value))).parse(input)?;
+    Ok((input, (key.to_string(), raw_value.map(unescape_tag_value))))
+}
+
+/// `@`-prefixed, semicolon-separated list of IRCv3 message tags.
+fn tags(input: &str) -> IResult<&str, Vec<(String, Option<String>)>> {
+    preceded(char('@'), separated_list1(char(';'), tag))(input)
+}
+
+fn command_token(input: &str) -> IResult<&str, String> {
+    alt((
+        map(alpha1, str::to_string),
+        map(recognize(count(satisfy(|c| c.is_ascii_digit()), 3)), str::to_string),
+    ))(input)
+}
+
+/// A middle parameter: `nospcrlfcl *( ":" / nospcrlfcl )` — it can't start
+/// with `:` (that introduces the trailing parameter instead) but may
+/// contain one after its first character.
+fn middle_param(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        satisfy(|c| !matches!(c, ' ' | ':' | '\0' | '\r' | '\n')),
+        many0(satisfy(|c| !matches!(c, ' ' | '\0' | '\r' | '\n'))),
+    ))(input)
+}
+
+/// The trailing parameter, introduced by `" :"`: unlike a middle parameter
+/// it may contain spaces and `:` freely, and may be empty.
+fn trailing_param(input: &str) -> IResult<&str, &str> {
+    recognize(many0(satisfy(|c| !matches!(c, '\0' | '\r' | '\n'))))(input)
+}
+
+fn params(input: &str) -> IResult<&str, Vec<String>> {
+    let (input, middles) = many0(preceded(space1, middle_param))(input)?;
+    let (input, trailing) = opt(preceded(pair(space1, char(':')), trailing_param))(input)?;
+    let mut params: Vec<String> = middles.into_iter().map(str::to_string).collect();
+    if let Some(trailing) = trailing {
+        params.push(trailing.to_string());
+    }
+    Ok((input, params))
+}
+
+/// A message source: a `nick[!user]@host` mask (reusing [`nickname`],
+/// [`user`] and [`host`]), or failing that a bare server name.
+fn source(input: &str) -> IResult<&str, Source> {
+    if let Ok((rest, (nick, user, host))) =
+        (nickname, opt(preceded(char('!'), user)), preceded(char('@'), host)).parse(input)
+    {
+        return Ok((
+            rest,
+            Source::User {
+                nick: nick.to_string(),
+                user: user.map(str::to_string),
+                host,
+            },
+        ));
+    }
+    map(host, Source::Server)(input)
+}
+
+/// Parses a complete IRC protocol line into a structured [`Message`]:
+/// `[ "@" tags SPACE ] [ ":" source SPACE ] command params [CRLF]`. Unlike
+/// [`command`] and [`user_prefix`] above, this is meant to be run directly
+/// on lines read off the wire. Not yet wired into the connection loop,
+/// which still goes through the `irc` crate's own parser; it lives here so
+/// callers can adopt it incrementally.
+#[allow(dead_code)]
+pub(crate) fn message(input: &str) -> Result<Message, MessageParseError> {
+    let input = input
+        .strip_suffix("\r\n")
+        .or_else(|| input.strip_suffix('\n'))
+        .unwrap_or(input);
+
+    let (input, tags) = opt(terminated(tags, space1))(input).map_err(|_: nom::Err<nom::error::Error<&str>>| MessageParseError::InvalidTags)?;
+    let (input, source) = opt(terminated(preceded(char(':'), source), space1))(input)
+        .map_err(|_: nom::Err<nom::error::Error<&str>>| MessageParseError::InvalidSource)?;
+    let (input, command) =
+        command_token(input).map_err(|_: nom::Err<nom::error::Error<&str>>| MessageParseError::InvalidCommand)?;
+    let (input, params) =
+        params(input).map_err(|_: nom::Err<nom::error::Error<&str>>| MessageParseError::InvalidParams)?;
+
+    if !input.is_empty() {
+        return Err(MessageParseError::TrailingInput(input.to_string()));
+    }
+
+    Ok(Message {
+        tags: tags.unwrap_or_default(),
+        source,
+        command,
+        params,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,16 +511,85 @@ mod tests {
 
     #[test]
     fn parse_host() {
-        assert_eq!(host("hello"), Ok(("", "hello")));
-        assert_eq!(host("example.com"), Ok(("", "example.com")));
-        assert_eq!(host("0:0:0:0:0:0:0:0"), Ok(("", "0:0:0:0:0:0:0:0")));
+        assert_eq!(host("hello"), Ok(("", "hello".to_string())));
+        assert_eq!(host("example.com"), Ok(("", "example.com".to_string())));
+        assert_eq!(
+            host("0:0:0:0:0:0:0:0"),
+            Ok(("", "0:0:0:0:0:0:0:0".to_string()))
+        );
+        // An internationalized domain name is normalized to its punycode
+        // A-label form rather than rejected outright.
+        assert_eq!(host("münchen.de"), Ok(("", "xn--mnchen-3ya.de".to_string())));
+        assert!(matches!(host(""), Err(_)));
     }
 
     #[test]
     fn parse_user_prefix() {
         assert_eq!(
             user_prefix("abc!def@example.com"),
-            Ok(("abc", "def", "example.com"))
+            Ok(("abc", "def", "example.com".to_string()))
         );
     }
+
+    #[test]
+    fn parse_message_plain() {
+        let message = message("PRIVMSG #channel :hello world").unwrap();
+        assert_eq!(message.tags, vec![]);
+        assert_eq!(message.source, None);
+        assert_eq!(message.command, "PRIVMSG");
+        assert_eq!(
+            message.params,
+            vec!["#channel".to_string(), "hello world".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_message_with_source_and_tags() {
+        let message =
+            message("@time=2023-08-10T12:00:00.000Z;msgid=abc\\sdef :nick!user@host.example PRIVMSG #chan :hi")
+                .unwrap();
+        assert_eq!(
+            message.tags,
+            vec![
+                ("time".to_string(), Some("2023-08-10T12:00:00.000Z".to_string())),
+                ("msgid".to_string(), Some("abc def".to_string())),
+            ]
+        );
+        assert_eq!(
+            message.source,
+            Some(Source::User {
+                nick: "nick".to_string(),
+                user: Some("user".to_string()),
+                host: "host.example".to_string(),
+            })
+        );
+        assert_eq!(message.command, "PRIVMSG");
+        assert_eq!(
+            message.params,
+            vec!["#chan".to_string(), "hi".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_message_numeric_command_no_trailing() {
+        let message = message(":irc.example 001 nick").unwrap();
+        assert_eq!(
+            message.source,
+            Some(Source::Server("irc.example".to_string()))
+        );
+        assert_eq!(message.command, "001");
+        assert_eq!(message.params, vec!["nick".to_string()]);
+    }
+
+    #[test]
+    fn parse_message_trailing_may_contain_spaces() {
+        let message = message(":irc.example PING :tok en\r\n").unwrap();
+        assert_eq!(message.params, vec!["tok en".to_string()]);
+    }
+
+    #[test]
+    fn parse_message_rejects_empty_command() {
+        assert!(message("").is_err());
+        assert!(message(":irc.example").is_err());
+    }
 }
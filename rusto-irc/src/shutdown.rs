@@ -0,0 +1,35 @@
+//! The single shutdown "tripwire" shared by every long-running task in the
+//! bot: the IRC read loop, the epoch timer thread, the web server, and each
+//! in-flight command task. Cloning a [`Shutdown`] shares the same
+//! underlying token, so any clone can fire it and every clone observes it.
+
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shutdown {
+    token: CancellationToken,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trips the tripwire. Safe to call more than once; only the first call
+    /// has any effect, so callers don't need to track whether it already
+    /// fired.
+    pub(crate) fn fire(&self) {
+        self.token.cancel();
+    }
+
+    pub(crate) fn is_tripped(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once [`fire`](Self::fire) has been called. Meant to be used
+    /// as the other branch of a `tokio::select!` around whatever a task is
+    /// normally waiting on.
+    pub(crate) async fn tripped(&self) {
+        self.token.cancelled().await;
+    }
+}
@@ -0,0 +1,169 @@
+//! Abstracts the outgoing half of an IRC connection behind [`IrcTransport`],
+//! so `Connection` and the command-dispatch code in `bot` can be driven by
+//! [`MockTransport`] in tests instead of a live `irc::client::Client`. The
+//! inbound half doesn't need its own abstraction: both a real client's
+//! `ClientStream` and a scripted test stream are just
+//! `Stream<Item = irc::error::Result<Message>>`, boxed into an
+//! [`InboundStream`].
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use futures::Stream;
+use irc::client::Client;
+use irc::error::Result as IrcResult;
+use irc::proto::Command;
+
+/// A boxed stream of inbound messages, produced either by a real
+/// `ClientStream` or by [`mock_inbound`] in tests.
+pub(crate) type InboundStream = Pin<Box<dyn Stream<Item = IrcResult<irc::proto::Message>> + Send>>;
+
+/// The outgoing operations `Connection` needs from an IRC client. Narrow on
+/// purpose: it only covers what the bot actually sends, not the rest of
+/// `irc::client::Client`'s surface.
+pub(crate) trait IrcTransport: Send + Sync {
+    fn send_privmsg(&self, target: &str, message: &str) -> IrcResult<()>;
+    fn send_join(&self, channels: &str, key: Option<&str>) -> IrcResult<()>;
+    fn send_part(&self, channels: &str, reason: Option<&str>) -> IrcResult<()>;
+    fn send_nick(&self, new_nick: &str) -> IrcResult<()>;
+    fn send_topic(&self, channel: &str, topic: &str) -> IrcResult<()>;
+    fn send_quit(&self, message: &str) -> IrcResult<()>;
+}
+
+impl IrcTransport for Client {
+    fn send_privmsg(&self, target: &str, message: &str) -> IrcResult<()> {
+        Client::send_privmsg(self, target, message)
+    }
+
+    fn send_join(&self, channels: &str, key: Option<&str>) -> IrcResult<()> {
+        Client::send(self, Command::JOIN(channels.to_string(), key.map(str::to_string), None))
+    }
+
+    fn send_part(&self, channels: &str, reason: Option<&str>) -> IrcResult<()> {
+        Client::send(self, Command::PART(channels.to_string(), reason.map(str::to_string)))
+    }
+
+    fn send_nick(&self, new_nick: &str) -> IrcResult<()> {
+        Client::send(self, Command::NICK(new_nick.to_string()))
+    }
+
+    fn send_topic(&self, channel: &str, topic: &str) -> IrcResult<()> {
+        Client::send(self, Command::TOPIC(channel.to_string(), Some(topic.to_string())))
+    }
+
+    fn send_quit(&self, message: &str) -> IrcResult<()> {
+        Client::send_quit(self, message)
+    }
+}
+
+/// One message [`MockTransport`] recorded, for tests to assert against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SentMessage {
+    Privmsg { target: String, message: String },
+    Join { channels: String, key: Option<String> },
+    Part { channels: String, reason: Option<String> },
+    Nick { new_nick: String },
+    Topic { channel: String, topic: String },
+    Quit { message: String },
+}
+
+/// An [`IrcTransport`] that records everything sent through it instead of
+/// talking to a server, so tests can drive a full command flow and assert on
+/// what would have gone out over the wire.
+#[derive(Default)]
+pub(crate) struct MockTransport {
+    sent: Mutex<Vec<SentMessage>>,
+}
+
+impl MockTransport {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything sent so far, in order.
+    pub(crate) fn sent(&self) -> Vec<SentMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl IrcTransport for MockTransport {
+    fn send_privmsg(&self, target: &str, message: &str) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Privmsg {
+            target: target.to_string(),
+            message: message.to_string(),
+        });
+        Ok(())
+    }
+
+    fn send_join(&self, channels: &str, key: Option<&str>) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Join {
+            channels: channels.to_string(),
+            key: key.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    fn send_part(&self, channels: &str, reason: Option<&str>) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Part {
+            channels: channels.to_string(),
+            reason: reason.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    fn send_nick(&self, new_nick: &str) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Nick {
+            new_nick: new_nick.to_string(),
+        });
+        Ok(())
+    }
+
+    fn send_topic(&self, channel: &str, topic: &str) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Topic {
+            channel: channel.to_string(),
+            topic: topic.to_string(),
+        });
+        Ok(())
+    }
+
+    fn send_quit(&self, message: &str) -> IrcResult<()> {
+        self.sent.lock().unwrap().push(SentMessage::Quit {
+            message: message.to_string(),
+        });
+        Ok(())
+    }
+}
+
+// Lets tests keep an `Arc<MockTransport>` around to inspect `sent()` after
+// handing an owned transport off to `Connection::set_client`.
+impl<T: IrcTransport + ?Sized> IrcTransport for Arc<T> {
+    fn send_privmsg(&self, target: &str, message: &str) -> IrcResult<()> {
+        T::send_privmsg(self, target, message)
+    }
+
+    fn send_join(&self, channels: &str, key: Option<&str>) -> IrcResult<()> {
+        T::send_join(self, channels, key)
+    }
+
+    fn send_part(&self, channels: &str, reason: Option<&str>) -> IrcResult<()> {
+        T::send_part(self, channels, reason)
+    }
+
+    fn send_nick(&self, new_nick: &str) -> IrcResult<()> {
+        T::send_nick(self, new_nick)
+    }
+
+    fn send_topic(&self, channel: &str, topic: &str) -> IrcResult<()> {
+        T::send_topic(self, channel, topic)
+    }
+
+    fn send_quit(&self, message: &str) -> IrcResult<()> {
+        T::send_quit(self, message)
+    }
+}
+
+/// Wraps a fixed, scripted list of inbound messages as an [`InboundStream`],
+/// for feeding `irc_stream_handler` in tests without a live connection.
+pub(crate) fn mock_inbound(messages: Vec<irc::proto::Message>) -> InboundStream {
+    Box::pin(futures::stream::iter(messages.into_iter().map(Ok)))
+}
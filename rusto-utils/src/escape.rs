@@ -0,0 +1,57 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+/// Wraps a byte slice so its `Display`/`Debug` output is always safe to
+/// write to a terminal or log: printable ASCII passes through unchanged,
+/// the usual C-style escapes stand in for tab/CR/LF/backslash, and every
+/// other control byte (`0x00..=0x1F`, `0x7F`) or non-ASCII byte is rendered
+/// as `\xNN`. Unlike `str::escape_debug`, this works off raw bytes, so it
+/// can't be fooled into passing through something that merely looks like
+/// printable Unicode.
+///
+/// # Examples
+///
+/// ```
+/// use rusto_utils::escape::escape_str;
+/// assert_eq!(escape_str("tab\there").to_string(), "tab\\there");
+/// assert_eq!(
+///     escape_str("\x1b[31mred\x1b[0m").to_string(),
+///     "\\x1b[31mred\\x1b[0m"
+/// );
+/// ```
+pub struct Escaped<'a>(&'a [u8]);
+
+impl<'a> Escaped<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &byte in self.0 {
+            match byte {
+                b'\t' => f.write_str("\\t")?,
+                b'\r' => f.write_str("\\r")?,
+                b'\n' => f.write_str("\\n")?,
+                b'\\' => f.write_str("\\\\")?,
+                0x20..=0x7E => f.write_char(byte as char)?,
+                _ => write!(f, "\\x{byte:02x}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+/// Shorthand for [`Escaped::new`] over a `&str`'s bytes — the common case of
+/// escaping a parsed identifier (a nickname, a command argument) for a log
+/// line or error message.
+pub fn escape_str(s: &str) -> Escaped<'_> {
+    Escaped::new(s.as_bytes())
+}
@@ -295,7 +295,7 @@ mod state {
                         let load_result = if module_name.trim().starts_with("https://") {
                             state.rustico().load_module_from_url(&module_name).await
                         } else {
-                            state.rustico().load_module(module_name.clone()).await
+                            state.rustico().load_module(module_name.clone(), false).await
                         };
                         let response = match load_result {
                             Ok(name) => format!("loaded module: {name}"),
@@ -658,7 +658,7 @@ async fn web_server(state: std::sync::Weak<BotState>) {
                 let state = state.clone();
                 async move {
                     let Some(state) = state.upgrade() else { return; };
-                    match state.rustico().load_module(module.clone()).await {
+                    match state.rustico().load_module(module.clone(), false).await {
                         Ok(_) => info!(module, "loaded module"),
                         Err(err) => error!(module, %err, "cannot load module"),
                     };